@@ -0,0 +1,5 @@
+// Per-draw parameters, mirrors `DrawPushConstants` in src/vk_push.rs.
+layout(push_constant) uniform DrawPushConstants {
+    mat4 transform;
+    vec4 tint;
+} draw;
@@ -1,9 +1,15 @@
 #version 450
+#extension GL_GOOGLE_include_directive : require
 
 layout(location = 0) out vec4 outColor;
 
+#include "draw_push_constants.glsl"
+
+layout(binding = 1) uniform sampler2D texSampler;
+
 layout(location = 0) in vec3 color;
+layout(location = 1) in vec2 texCoord;
 
 void main() {
-    outColor = vec4(color, 1.0);
+    outColor = vec4(color, 1.0) * texture(texSampler, texCoord) * draw.tint;
 }
\ No newline at end of file
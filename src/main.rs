@@ -1,9 +1,204 @@
 mod vk_app;
 mod vk_bindings;
+mod vk_depth;
+mod vk_device;
+mod vk_error;
+mod vk_features;
+mod vk_handles;
+mod vk_mesh;
+mod vk_pipeline_cache;
+mod vk_push;
+mod vk_reflect;
+mod vk_shader;
+mod vk_texture;
+mod vk_uniform;
+mod vk_version;
 
-fn main() -> Result<(), ()> {
+use std::path::PathBuf;
+use std::str::FromStr;
+use vk_error::{AppError, Stage};
+
+fn main() -> Result<(), AppError> {
+    let args: Vec<String> = std::env::args().collect();
     let mut app = vk_app::HelloTriangleApplication::default();
-    app.run();
+    let api_version = flag_value(&args, "--api-version")
+        .map(|value| ("--api-version", value.to_string()))
+        .or_else(|| std::env::var(vk_version::API_VERSION_VAR).ok().map(|value| (vk_version::API_VERSION_VAR, value)));
+    if let Some((name, api_version)) = api_version {
+        let ceiling = vk_version::parse(&api_version)
+            .ok_or_else(|| invalid_argument(name, &api_version, "a version like 1.2"))?;
+        app.set_api_version_ceiling(ceiling);
+    }
+    let headless = args.iter().any(|arg| arg == "--headless");
+    if args.iter().any(|arg| arg == "--list-devices") {
+        let candidates = app.list_devices(headless)?;
+        println!("instance: vulkan {}", vk_version::name(app.api_version()));
+        for candidate in candidates {
+            println!("{}", candidate);
+        }
+        return Ok(());
+    }
+    let device = flag_value(&args, "--device")
+        .map(str::to_string)
+        .or_else(|| std::env::var(vk_device::DEVICE_VAR).ok());
+    if let Some(device) = device {
+        app.set_device_selector(vk_device::DeviceSelector::parse(&device));
+    }
+    for name in flag_values(&args, "--require-extension") {
+        app.require_device_extension(name)?;
+    }
+    for name in flag_values(&args, "--request-extension") {
+        app.request_device_extension(name)?;
+    }
+    let mut device_requirements = vk_device::DeviceRequirements::default();
+    for name in flag_values(&args, "--require-feature") {
+        if !device_requirements.required_features.enable(name) {
+            let expected = "a feature like samplerAnisotropy or vulkan12.timelineSemaphore";
+            return Err(invalid_argument("--require-feature", name, expected));
+        }
+    }
+    app.set_device_requirements(device_requirements);
+    if let Some(samples) = parse_flag(&args, "--samples", "a sample count")? {
+        app.set_msaa_samples(samples);
+    }
+    if let Some(frames_in_flight) = parse_flag(&args, "--frames-in-flight", "a number of frames")? {
+        app.set_frames_in_flight(frames_in_flight);
+    }
+    match flag_value(&args, "--mesh") {
+        Some("triangle") | None => (),
+        Some("quad") => app.set_mesh(vk_mesh::Mesh::quad()),
+        Some(mesh) => return Err(invalid_argument("--mesh", mesh, "triangle or quad"))
+    }
+    if let Some(depth) = flag_value(&args, "--depth") {
+        let depth_state = vk_depth::DepthState::parse(depth)
+            .ok_or_else(|| invalid_argument("--depth", depth, "a compare op like less-or-equal, or off"))?;
+        app.set_depth_state(depth_state);
+    }
+    if let Some(tint) = flag_value(&args, "--tint") {
+        let draw_push_constants = vk_push::DrawPushConstants {tint: parse_tint(tint)?, ..Default::default()};
+        let range = vk_push::DrawPushConstants::range();
+        app.set_draw_hook(move |draw| draw.push_constants(range.stages, range.offset, &draw_push_constants));
+    }
+    if let Some(path) = flag_value(&args, "--texture") {
+        app.set_texture(vk_texture::TextureData::load(path)?);
+    }
+    let min_lod = parse_flag(&args, "--min-lod", "a level of detail")?;
+    let max_lod = parse_flag(&args, "--max-lod", "a level of detail")?;
+    let mip_lod_bias = parse_flag(&args, "--lod-bias", "a level of detail bias")?;
+    if min_lod.is_some() || max_lod.is_some() || mip_lod_bias.is_some() {
+        let default = vk_texture::SamplerLod::default();
+        app.set_sampler_lod(vk_texture::SamplerLod {
+            min_lod: min_lod.unwrap_or(default.min_lod),
+            max_lod: max_lod.or(default.max_lod),
+            mip_lod_bias: mip_lod_bias.unwrap_or(default.mip_lod_bias)
+        });
+    }
+    if args.iter().any(|arg| arg == "--no-pipeline-cache") {
+        app.disable_pipeline_cache();
+    } else if let Some(path) = flag_value(&args, "--pipeline-cache") {
+        app.set_pipeline_cache_path(PathBuf::from(path));
+    }
+    let shader_dir = flag_value(&args, "--shader-dir")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os(vk_shader::SHADER_DIR_VAR).map(PathBuf::from));
+    let vertex_shader = flag_value(&args, "--vertex-shader").map(PathBuf::from);
+    let fragment_shader = flag_value(&args, "--fragment-shader").map(PathBuf::from);
+    if vertex_shader.is_some() || fragment_shader.is_some() {
+        // a stage without its own file keeps the embedded shader
+        app.set_shaders(
+            vertex_shader.map_or(vk_shader::ShaderSource::Embedded("shader.vert"), vk_shader::ShaderSource::from_file),
+            fragment_shader.map_or(vk_shader::ShaderSource::Embedded("shader.frag"), vk_shader::ShaderSource::from_file)
+        );
+    } else if let Some(shader_dir) = shader_dir {
+        app.set_shader_dir(&shader_dir)?;
+    }
+
+    if headless {
+        let (width, height) = match flag_value(&args, "--extent") {
+            Some(extent) => parse_extent(extent)?,
+            None => (vk_app::WIDTH as u32, vk_app::HEIGHT as u32)
+        };
+
+        let pixels = app.render_offscreen(width, height)?;
+
+        match flag_value(&args, "--output") {
+            Some(path) => image::save_buffer(path, &pixels, width, height, image::ColorType::Rgba8)
+                .map_err(|error| AppError::Write {
+                    stage: Stage::Output,
+                    path: path.to_string(),
+                    source: match error {
+                        image::ImageError::IoError(source) => source,
+                        error => std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+                    }
+                })?,
+            None => println!("rendered {}x{} offscreen ({} bytes)", width, height, pixels.len())
+        }
+    } else {
+        app.run()?;
+    }
+    if args.iter().any(|arg| arg == "--verbose") {
+        print_summary(&app);
+    }
 
     Ok(())
+}
+
+
+/// Prints what the device was set up with. Runs after rendering, so the memory usage includes the frame's resources.
+fn print_summary(app: &vk_app::HelloTriangleApplication) {
+    println!(
+        "vulkan {} instance, used at {} on the device",
+        vk_version::name(app.api_version()), vk_version::name(app.device_api_version())
+    );
+    println!("device extensions: {}", app.enabled_device_extensions().join(", "));
+    println!("device features: {}", app.enabled_features().names().join(", "));
+    println!("transfer queue: {:?}, compute queue: {:?}", app.transfer_queue(), app.compute_queue());
+    for (index, heap) in app.memory_budget().unwrap_or_default().iter().enumerate() {
+        println!(
+            "memory heap {}: {} MiB used of {} MiB, budget {} MiB",
+            index, heap.usage >> 20, heap.size >> 20, heap.budget >> 20
+        );
+    }
+}
+
+
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let position = args.iter().position(|arg| arg == flag)?;
+    args.get(position + 1).map(|value| value.as_str())
+}
+
+
+/// The values of every occurrence of `flag`, for flags that can be repeated.
+fn flag_values<'a>(args: &'a [String], flag: &'a str) -> impl Iterator<Item = &'a str> {
+    args.windows(2).filter(move |pair| pair[0] == flag).map(|pair| pair[1].as_str())
+}
+
+
+/// Parses the value of `flag` if it is given.
+fn parse_flag<T: FromStr>(args: &[String], flag: &str, expected: &str) -> Result<Option<T>, AppError> {
+    flag_value(args, flag)
+        .map(|value| value.parse().map_err(|_| invalid_argument(flag, value, expected)))
+        .transpose()
+}
+
+
+/// Parses `WIDTHxHEIGHT`, e.g. `256x256`.
+fn parse_extent(extent: &str) -> Result<(u32, u32), AppError> {
+    extent.split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .ok_or_else(|| invalid_argument("--extent", extent, "WIDTHxHEIGHT, e.g. 256x256"))
+}
+
+
+/// Parses `R,G,B,A`, e.g. `1,0.5,0.5,1`.
+fn parse_tint(tint: &str) -> Result<[f32; 4], AppError> {
+    let channels: Option<Vec<f32>> = tint.split(',').map(|channel| channel.trim().parse().ok()).collect();
+    channels
+        .and_then(|channels| channels.try_into().ok())
+        .ok_or_else(|| invalid_argument("--tint", tint, "R,G,B,A, e.g. 1,0.5,0.5,1"))
+}
+
+
+fn invalid_argument(name: &str, value: &str, expected: &str) -> AppError {
+    AppError::InvalidArgument {name: name.to_string(), value: value.to_string(), expected: expected.to_string()}
 }
\ No newline at end of file
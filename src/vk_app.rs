@@ -1,17 +1,43 @@
 use glfw::ffi::*;
 use vulkan::vulkan as vk;
+use crate::vk_depth::{has_stencil_component, DepthState, DEPTH_FORMAT_CANDIDATES};
+use crate::vk_device::{
+    extension_features, select, DeviceCandidate, DeviceExtensions, DeviceRequirements, DeviceSelector,
+    FormatRequirement, HeapBudget, MinimumLimit, Suitability, MEMORY_BUDGET_EXTENSION, SWAPCHAIN_EXTENSION
+};
+use crate::vk_features::DeviceFeatures;
+use crate::vk_error::{check, result_name, unsupported, AppError, Result, Stage};
+use crate::vk_handles::*;
+use crate::vk_mesh::{Mesh, Vertex};
+use crate::vk_pipeline_cache;
+use crate::vk_shader::{check_shader_dir, ShaderSource, ShaderWatcher};
+use crate::vk_texture::{SamplerLod, TextureData};
+use crate::vk_push::{DrawContext, DrawHook, DrawPushConstants, PushConstantRange};
+use crate::vk_reflect::{reflect, PipelineInterface};
+use crate::vk_uniform::{aspect_correction, UniformBufferObject, IDENTITY};
+use crate::vk_version::{self, make_api_version};
+use std::path::{Path, PathBuf};
 use std::ptr::{null, null_mut};
-use std::collections::{BTreeMap, HashSet};
 
-const WIDTH:i32 = 800;
-const HEIGHT:i32 = 600;
+pub const WIDTH:i32 = 800;
+pub const HEIGHT:i32 = 600;
 
-const VALIDATION_LAYERS: [&str; 1] = [
-    "VK_LAYER_KHRONOS_validation"
+const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+
+// texture pixels are stored in sRGB, so sampling returns linear values
+const TEXTURE_FORMAT: vk::VkFormat = vk::VK_FORMAT_R8G8B8A8_SRGB;
+
+// RGBA byte order, so read back pixels can be handed to image encoders without swizzling
+const OFFSCREEN_FORMAT: vk::VkFormat = vk::VK_FORMAT_R8G8B8A8_UNORM;
+
+// descriptors the application fills in, by binding in set 0
+const PROVIDED_DESCRIPTORS: [(u32, vk::VkDescriptorType); 2] = [
+    (0, vk::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER),
+    (1, vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER)
 ];
 
-const DEVICE_EXTENSIONS: [*const u8; 1] = [
-    vk::VK_KHR_SWAPCHAIN_EXTENSION_NAME.as_ptr()
+const VALIDATION_LAYERS: [&str; 1] = [
+    "VK_LAYER_KHRONOS_validation"
 ];
 
 #[cfg(debug_assertions)]
@@ -20,207 +46,781 @@ const ENABLE_VALIDATION_LAYERS: bool = true;
 #[cfg(not(debug_assertions))]
 const ENABLE_VALIDATION_LAYERS: bool = false;
 
+// Fields are dropped in declaration order, so every owned handle is declared
+// before the handles it was created from.
 #[derive(Default)]
 pub struct HelloTriangleApplication {
-    window: *mut GLFWwindow,
-    instance: vk::VkInstance,
-    debug_messenger: vk::VkDebugUtilsMessengerEXT,
+    in_flight_fences: Vec<Fence>,
+    render_finished_semaphores: Vec<Semaphore>,
+    image_available_semaphores: Vec<Semaphore>,
+    command_pool: CommandPool,
+    // on the transfer family, which is the graphics family on devices without a dedicated one
+    transfer_command_pool: CommandPool,
+    descriptor_pool: DescriptorPool,
+    swap_chain_framebuffers: Vec<Framebuffer>,
+    pipeline: Pipeline,
+    pipeline_layout: PipelineLayout,
+    pipeline_cache: PipelineCache,
+    descriptor_set_layout: DescriptorSetLayout,
+    render_pass: RenderPass,
+    swap_chain_image_views: Vec<ImageView>,
+    color_image_view: ImageView,
+    color_image: Image,
+    color_image_memory: DeviceMemory,
+    depth_image_view: ImageView,
+    depth_image: Image,
+    depth_image_memory: DeviceMemory,
+    offscreen_image: Image,
+    offscreen_image_memory: DeviceMemory,
+    texture_sampler: Sampler,
+    texture_image_view: ImageView,
+    texture_image: Image,
+    texture_image_memory: DeviceMemory,
+    vertex_buffer: Buffer,
+    vertex_buffer_memory: DeviceMemory,
+    index_buffer: Buffer,
+    index_buffer_memory: DeviceMemory,
+    uniform_buffers: Vec<Buffer>,
+    uniform_buffers_memory: Vec<DeviceMemory>,
+    swap_chain: Swapchain,
+    device: Device,
+    surface: Surface,
+    debug_messenger: DebugMessenger,
+    instance: Instance,
+    window: Window,
+
     physical_device: vk::VkPhysicalDevice,
-    device: vk::VkDevice,
+    queue_family_indices: QueueFamilyIndices,
     graphics_queue: vk::VkQueue,
-    surface: vk::VkSurfaceKHR,
     present_queue: vk::VkQueue,
-    swap_chain: vk::VkSwapchainKHR,
+    transfer_queue: vk::VkQueue,
+    compute_queue: vk::VkQueue,
     swap_chain_images: Vec<vk::VkImage>,
     swap_chain_image_format: vk::VkFormat,
     swap_chain_extent: vk::VkExtent2D,
-    swap_chain_image_views: Vec<vk::VkImageView>,
-    render_pass: vk::VkRenderPass,
-    pipeline_layout: vk::VkPipelineLayout,
-    pipeline: vk::VkPipeline,
-    swap_chain_framebuffers: Vec<vk::VkFramebuffer>,
-    command_pool: vk::VkCommandPool,
-    command_buffer: vk::VkCommandBuffer,
-    image_available_semaphore: vk::VkSemaphore,
-    render_finished_semaphore: vk::VkSemaphore,
-    in_flight: vk::VkFence
+    depth_format: vk::VkFormat,
+    // 0 asks for single sampling
+    requested_msaa_samples: u32,
+    msaa_samples: vk::VkSampleCountFlagBits,
+    depth_state: DepthState,
+    // 0 keeps `DEFAULT_FRAMES_IN_FLIGHT`
+    requested_frames_in_flight: usize,
+    command_buffers: Vec<vk::VkCommandBuffer>,
+    // stays mapped until the memory is freed
+    uniform_buffers_mapped: Vec<*mut std::ffi::c_void>,
+    descriptor_sets: Vec<vk::VkDescriptorSet>,
+    images_in_flight: Vec<vk::VkFence>,
+    current_frame: usize,
+    framebuffer_resized: bool,
+    headless: bool,
+    mesh: Mesh,
+    shaders: Shaders,
+    // only watches while running in a window
+    shader_watcher: Option<ShaderWatcher>,
+    // reflected from the shaders the current pipeline was built from
+    pipeline_interface: PipelineInterface,
+    texture: TextureData,
+    texture_mip_levels: u32,
+    sampler_lod: SamplerLod,
+    draw_hook: Option<DrawHook>,
+    start_time: Option<std::time::Instant>,
+    // `None` keeps the cache at `vk_pipeline_cache::default_path`
+    pipeline_cache_path: Option<PathBuf>,
+    // the cache then lives only as long as the device, nothing is read or written
+    pipeline_cache_disabled: bool,
+    device_selector: DeviceSelector,
+    // asked for by the caller, the swapchain extension is added when presenting
+    device_extensions: DeviceExtensions,
+    enabled_device_extensions: Vec<String>,
+    // asked for by the caller, the application's own needs are added in `device_requirements`
+    device_requirements: DeviceRequirements,
+    enabled_features: DeviceFeatures,
+    // `None` asks for up to `vk_version::LATEST`
+    api_version_ceiling: Option<u32>,
+    // the version the instance was created with
+    api_version: u32,
+    // the version usable on the chosen device, at most `api_version`
+    device_api_version: u32
+}
+
+impl Drop for HelloTriangleApplication {
+    fn drop(&mut self) {
+        // a failed frame can leave work in flight, and nothing may be destroyed while the gpu still uses it
+        if !self.device.raw().is_null() {
+            unsafe{vk::vkDeviceWaitIdle(self.device.raw())};
+        }
+        self.save_pipeline_cache();
+    }
 }
 
 impl HelloTriangleApplication {
-    pub fn run(&mut self) {
-        self.init_window();
-        self.init_vulkan();
-        self.main_loop();
-        self.cleanup();
+    /// Replaces the geometry that gets drawn, which defaults to `Mesh::triangle`.
+    /// Has to be called before `run` or `render_offscreen`.
+    pub fn set_mesh(&mut self, mesh: Mesh) {
+        self.mesh = mesh;
+    }
+
+
+    /// Replaces the shaders of the pipeline, which default to compiling the embedded `shaders/shader.vert` and
+    /// `shaders/shader.frag` at runtime. Has to be called before `run` or `render_offscreen`.
+    pub fn set_shaders(&mut self, vertex: ShaderSource, fragment: ShaderSource) {
+        self.shaders = Shaders {vertex, fragment};
+    }
+
+
+    /// Takes the GLSL files `shader.vert` and `shader.frag` from `shader_dir` instead of the embedded shaders, each
+    /// one only if the directory has it. SPIR-V files in the directory are not picked up, `set_shaders` takes those.
+    /// Fails if `shader_dir` is not a directory. Has to be called before `run` or `render_offscreen`.
+    pub fn set_shader_dir(&mut self, shader_dir: &Path) -> Result<()> {
+        check_shader_dir(shader_dir)?;
+        self.shaders = Shaders {
+            vertex: ShaderSource::from_dir_or_embedded("shader.vert", shader_dir),
+            fragment: ShaderSource::from_dir_or_embedded("shader.frag", shader_dir)
+        };
+
+        Ok(())
+    }
+
+
+    /// Replaces the texture sampled by the fragment shader, which defaults to `TextureData::white`.
+    /// Has to be called before `run` or `render_offscreen`.
+    pub fn set_texture(&mut self, texture: TextureData) {
+        self.texture = texture;
+    }
+
+
+    /// Replaces the level of detail settings of the texture sampler.
+    /// Has to be called before `run` or `render_offscreen`.
+    pub fn set_sampler_lod(&mut self, sampler_lod: SamplerLod) {
+        self.sampler_lod = sampler_lod;
+    }
+
+
+    /// Requests multisampling with `samples` samples per pixel. The count is clamped to the largest one the device
+    /// supports for both color and depth framebuffer attachments, and 1 disables multisampling.
+    /// Has to be called before `run` or `render_offscreen`.
+    pub fn set_msaa_samples(&mut self, samples: u32) {
+        self.requested_msaa_samples = samples;
     }
 
 
-    fn init_window(&mut self) {
+    /// Sets how many frames can be recorded while earlier ones are still rendered, which defaults to 2.
+    /// 1 waits for every frame to finish before recording the next one. Has to be called before `run`.
+    pub fn set_frames_in_flight(&mut self, frames_in_flight: usize) {
+        self.requested_frames_in_flight = frames_in_flight;
+    }
+
+
+    /// Replaces the depth test state of the pipeline, which defaults to testing and writing with `LESS`.
+    /// Has to be called before `run` or `render_offscreen`.
+    pub fn set_depth_state(&mut self, depth_state: DepthState) {
+        self.depth_state = depth_state;
+    }
+
+
+    /// Chooses the physical device, which defaults to the suitable one with the highest score.
+    /// Has to be called before `run` or `render_offscreen`.
+    pub fn set_device_selector(&mut self, device_selector: DeviceSelector) {
+        self.device_selector = device_selector;
+    }
+
+
+    /// Rejects devices without the device extension `name` and enables it on the chosen one, along with the
+    /// extensions it depends on. Fails if the application does not know how to enable `name`.
+    /// Has to be called before `run` or `render_offscreen`.
+    pub fn require_device_extension(&mut self, name: &str) -> Result<()> {
+        self.device_extensions.require(name)
+            .map_err(|context| AppError::Unsupported {stage: Stage::PhysicalDevice, context})
+    }
+
+
+    /// Enables the device extension `name` if the chosen device has it and the extensions it depends on, see
+    /// `is_device_extension_enabled`. Fails if the application does not know how to enable `name`.
+    /// Has to be called before `run` or `render_offscreen`.
+    pub fn request_device_extension(&mut self, name: &str) -> Result<()> {
+        self.device_extensions.request(name)
+            .map_err(|context| AppError::Unsupported {stage: Stage::PhysicalDevice, context})
+    }
+
+
+    /// Whether the logical device was created with the extension `name`.
+    pub fn is_device_extension_enabled(&self, name: &str) -> bool {
+        self.enabled_device_extensions.iter().any(|enabled| enabled == name)
+    }
+
+
+    /// The extensions the logical device was created with, empty before it exists.
+    pub fn enabled_device_extensions(&self) -> &[String] {
+        &self.enabled_device_extensions
+    }
+
+
+    /// How much of each memory heap the application may use and uses, `None` unless the logical device was created
+    /// with `VK_EXT_memory_budget`.
+    pub fn memory_budget(&self) -> Option<Vec<HeapBudget>> {
+        if !self.is_device_extension_enabled(MEMORY_BUDGET_EXTENSION) {
+            return None;
+        }
+        // the extension is only enabled on Vulkan 1.1 instances, which have this in core
+        let option_fn_ptr = unsafe{std::mem::transmute::<_, vk::PFN_vkGetPhysicalDeviceMemoryProperties2>(
+            vk::vkGetInstanceProcAddr(self.instance.raw(), "vkGetPhysicalDeviceMemoryProperties2\0".as_ptr() as _))};
+        let fn_ptr = option_fn_ptr?;
+
+        let mut budget_properties = vk::VkPhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        budget_properties.sType = vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_BUDGET_PROPERTIES_EXT;
+        let mut memory_properties = vk::VkPhysicalDeviceMemoryProperties2::default();
+        memory_properties.sType = vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_MEMORY_PROPERTIES_2;
+        memory_properties.pNext = &mut budget_properties as *mut _ as _;
+        unsafe{fn_ptr(self.physical_device, &mut memory_properties)};
+
+        let heap_count = memory_properties.memoryProperties.memoryHeapCount as usize;
+        let heaps = (0..heap_count).map(|heap| HeapBudget {
+            size: memory_properties.memoryProperties.memoryHeaps[heap].size,
+            budget: budget_properties.heapBudget[heap],
+            usage: budget_properties.heapUsage[heap]
+        });
+
+        Some(heaps.collect())
+    }
+
+
+    /// Adds to what the chosen device has to offer. The application itself only needs the texture format to be
+    /// sampleable and the texture to fit `maxImageDimension2D`, and uses `samplerAnisotropy` when the device has it.
+    /// Has to be called before `run` or `render_offscreen`.
+    pub fn set_device_requirements(&mut self, device_requirements: DeviceRequirements) {
+        self.device_requirements = device_requirements;
+    }
+
+
+    /// The features the logical device was created with, all unset before it exists.
+    pub fn enabled_features(&self) -> &DeviceFeatures {
+        &self.enabled_features
+    }
+
+
+    /// Rates every physical device as `render_offscreen` would if `headless`, and as `run` would otherwise, which
+    /// opens the window to check presentation against its surface. Sets up the instance for that mode, so the
+    /// application is not meant to be run afterwards.
+    pub fn list_devices(&mut self, headless: bool) -> Result<Vec<DeviceCandidate>> {
+        self.headless = headless;
+        if !headless {
+            self.init_window()?;
+        }
+        self.create_instance()?;
+        if !headless {
+            self.create_surface()?;
+        }
+
+        self.device_candidates()
+    }
+
+
+    /// Caps the Vulkan version the instance is created with, e.g. at `vk_version::VULKAN_1_2`. By default the newest
+    /// version the loader supports is used, up to `vk_version::LATEST`.
+    /// Has to be called before `run` or `render_offscreen`.
+    pub fn set_api_version_ceiling(&mut self, ceiling: u32) {
+        self.api_version_ceiling = Some(ceiling);
+    }
+
+
+    /// The version the instance was created with, 0 before it exists.
+    pub fn api_version(&self) -> u32 {
+        self.api_version
+    }
+
+
+    /// The version whose core functionality can be used on the chosen device: the older of the instance version and
+    /// the version the device supports. 0 before the logical device exists.
+    pub fn device_api_version(&self) -> u32 {
+        self.device_api_version
+    }
+
+
+    /// The queue uploads are submitted to, the graphics queue on devices without a separate transfer family.
+    pub fn transfer_queue(&self) -> vk::VkQueue {
+        self.transfer_queue
+    }
+
+
+    /// The queue for asynchronous compute work, the graphics queue on devices without a separate compute family.
+    /// Nothing is submitted to it by the application itself.
+    pub fn compute_queue(&self) -> vk::VkQueue {
+        self.compute_queue
+    }
+
+
+    /// Keeps the pipeline cache in the file at `path` instead of the user's cache directory.
+    /// Has to be called before `run` or `render_offscreen`.
+    pub fn set_pipeline_cache_path(&mut self, path: PathBuf) {
+        self.pipeline_cache_path = Some(path);
+    }
+
+
+    /// Neither seeds the pipeline cache from a file nor saves it, e.g. for runs that must not touch the user's
+    /// files. Has to be called before `run` or `render_offscreen`.
+    pub fn disable_pipeline_cache(&mut self) {
+        self.pipeline_cache_disabled = true;
+    }
+
+
+    /// Runs `hook` while recording every draw, right before the draw command, e.g. to push parameters with
+    /// `DrawContext::push_constants`. Default `DrawPushConstants` are pushed before the hook runs, if the shaders
+    /// have their block. An error of the hook fails the frame. Has to be called before `run` or `render_offscreen`.
+    pub fn set_draw_hook(&mut self, hook: impl Fn(&DrawContext) -> Result<()> + 'static) {
+        self.draw_hook = Some(Box::new(hook));
+    }
+
+
+    pub fn run(&mut self) -> Result<()> {
+        self.init_window()?;
+        self.init_vulkan()?;
+        // embedded shaders cannot change, so there may be nothing to watch
+        let shader_paths: Vec<&Path> = [&self.shaders.vertex, &self.shaders.fragment]
+            .into_iter()
+            .filter_map(ShaderSource::path)
+            .collect();
+        if !shader_paths.is_empty() {
+            self.shader_watcher = Some(ShaderWatcher::new(&shader_paths));
+        }
+        self.start_time = Some(std::time::Instant::now());
+        self.main_loop()
+    }
+
+
+    /// Renders a single frame into an offscreen image without creating a window, surface or swapchain,
+    /// and returns the pixels as tightly packed RGBA8 rows.
+    pub fn render_offscreen(&mut self, width: u32, height: u32) -> Result<Vec<u8>> {
+        self.headless = true;
+        self.swap_chain_extent = vk::VkExtent2D{width, height};
+
+        self.init_headless()?;
+        self.draw_offscreen()
+    }
+
+
+    fn init_window(&mut self) -> Result<()> {
         unsafe {
-            glfwInit();
+            if glfwInit() != GLFW_TRUE {
+                return unsupported(Stage::Window, "failed to initialize GLFW");
+            }
             glfwWindowHint(GLFW_CLIENT_API, GLFW_NO_API);
-            glfwWindowHint(GLFW_RESIZABLE, GLFW_FALSE);
+            glfwWindowHint(GLFW_RESIZABLE, GLFW_TRUE);
 
-            self.window = glfwCreateWindow(
+            let window = glfwCreateWindow(
                 WIDTH, 
                 HEIGHT, 
                 "VK_app\0".as_ptr() as _, 
                 std::ptr::null_mut(), 
                 std::ptr::null_mut()
             );
+            if window.is_null() {
+                glfwTerminate();
+                return unsupported(Stage::Window, "failed to create window");
+            }
+            self.window = Window::new(window);
+
+            // the callback only gets the window, so it finds the app through the user pointer.
+            // events are only processed inside `run`, which borrows self mutably, so the address is stable.
+            glfwSetWindowUserPointer(self.window.raw(), self as *mut Self as _);
+            glfwSetFramebufferSizeCallback(self.window.raw(), Some(framebuffer_resize_callback));
         }
+
+        Ok(())
+    }
+
+
+    fn init_vulkan(&mut self) -> Result<()> {
+        self.create_instance()?;
+        self.setup_debug_messanger()?;
+        self.create_surface()?;
+        self.pick_physical_device()?;
+        self.create_logical_device()?;
+        self.create_pipeline_cache()?;
+        self.create_swapchain()?;
+        self.create_image_views()?;
+        self.create_render_pass()?;
+        self.create_graphics_pipeline()?;
+        self.create_color_resources()?;
+        self.create_depth_resources()?;
+        self.create_framebuffers()?;
+        self.create_command_pool()?;
+        self.create_vertex_buffer()?;
+        self.create_index_buffer()?;
+        self.create_texture_image()?;
+        self.create_texture_image_view()?;
+        self.create_texture_sampler()?;
+        self.create_uniform_buffers()?;
+        self.create_descriptor_pool()?;
+        self.create_descriptor_sets()?;
+        self.create_command_buffers()?;
+        self.create_sync_objects()
     }
 
 
-    fn init_vulkan(&mut self) {
-        self.create_instance();
-        self.setup_debug_messanger();
-        self.create_surface();
-        self.pick_physical_device();
-        self.create_logical_device();
-        self.create_swapchain();
-        self.create_image_views();
-        self.create_render_pass();
-        self.create_graphics_pipeline();
-        self.create_framebuffers();
-        self.create_command_pool();
-        self.create_command_buffer();
-        self.create_sync_objects();
+    fn init_headless(&mut self) -> Result<()> {
+        self.create_instance()?;
+        self.setup_debug_messanger()?;
+        self.pick_physical_device()?;
+        self.create_logical_device()?;
+        self.create_pipeline_cache()?;
+        self.create_offscreen_target()?;
+        self.create_render_pass()?;
+        self.create_graphics_pipeline()?;
+        self.create_color_resources()?;
+        self.create_depth_resources()?;
+        self.create_framebuffers()?;
+        self.create_command_pool()?;
+        self.create_vertex_buffer()?;
+        self.create_index_buffer()?;
+        self.create_texture_image()?;
+        self.create_texture_image_view()?;
+        self.create_texture_sampler()?;
+        self.create_uniform_buffers()?;
+        self.create_descriptor_pool()?;
+        self.create_descriptor_sets()?;
+        self.create_command_buffers()?;
+        self.create_sync_objects()
     }
 
     
 
-    fn  main_loop(&mut self) {
+    fn  main_loop(&mut self) -> Result<()> {
         unsafe {
-            while glfwWindowShouldClose(self.window) == 0 {
+            while glfwWindowShouldClose(self.window.raw()) == 0 {
                 glfwPollEvents();
-                self.draw_frame();
+                if self.shader_watcher.as_mut().is_some_and(ShaderWatcher::poll) {
+                    self.reload_graphics_pipeline()?;
+                }
+                self.draw_frame()?;
             }
 
-            vk::vkDeviceWaitIdle(self.device);
+            check(vk::vkDeviceWaitIdle(self.device.raw()), Stage::Draw, "failed to wait for the device to become idle")
         }
     }
 
 
-    fn cleanup(&mut self) {
+    /// Destroys everything that depends on the swapchain images, but not the swapchain itself,
+    /// so that it can still be handed to `create_swapchain` as the old swapchain.
+    fn cleanup_swapchain(&mut self) {
+        self.swap_chain_framebuffers.clear();
+        self.color_image_view = ImageView::default();
+        self.color_image = Image::default();
+        self.color_image_memory = DeviceMemory::default();
+        self.depth_image_view = ImageView::default();
+        self.depth_image = Image::default();
+        self.depth_image_memory = DeviceMemory::default();
+        self.swap_chain_image_views.clear();
+    }
+
+
+    fn recreate_swapchain(&mut self) -> Result<()> {
         unsafe {
-            vk::vkDestroySemaphore(self.device, self.image_available_semaphore, null());
-            vk::vkDestroySemaphore(self.device, self.render_finished_semaphore, null());
-            vk::vkDestroyFence(self.device, self.in_flight, null());
-            vk::vkDestroyCommandPool(self.device, self.command_pool, null());
-            for frame_buffer in &self.swap_chain_framebuffers {
-                vk::vkDestroyFramebuffer(self.device, *frame_buffer, null());
-            }
-            vk::vkDestroyPipeline(self.device, self.pipeline, null());
-            vk::vkDestroyPipelineLayout(self.device, self.pipeline_layout, null());
-            vk::vkDestroyRenderPass(self.device, self.render_pass, null());
-            for image_view in &self.swap_chain_image_views {
-                vk::vkDestroyImageView(self.device, *image_view, null());
-            }
-            vk::vkDestroySwapchainKHR(self.device, self.swap_chain, null());
-            vk::vkDestroyDevice(self.device, null());
-            if ENABLE_VALIDATION_LAYERS {
-                destroy_debug_utils_messenger(self.instance, self.debug_messenger, null());
+            // a minimized window has a 0x0 framebuffer, which is not a valid swapchain extent
+            let (mut width, mut height): (i32, i32) = (0, 0);
+            glfwGetFramebufferSize(self.window.raw(), &mut width, &mut height);
+            while width == 0 || height == 0 {
+                if glfwWindowShouldClose(self.window.raw()) != 0 {return Ok(())}
+                glfwWaitEvents();
+                glfwGetFramebufferSize(self.window.raw(), &mut width, &mut height);
             }
-            vk::vkDestroySurfaceKHR(self.instance, self.surface, null());
-            vk::vkDestroyInstance(self.instance, null());
-            glfwDestroyWindow(self.window);
-            glfwTerminate();
+
+            check(vk::vkDeviceWaitIdle(self.device.raw()), Stage::Swapchain, "failed to wait for the device to become idle")?;
         }
+
+        self.cleanup_swapchain();
+        self.create_swapchain()?;
+        self.create_image_views()?;
+        self.create_color_resources()?;
+        self.create_depth_resources()?;
+        self.create_framebuffers()?;
+
+        // the new swapchain may have a different image count, and none of its images are in flight yet
+        self.images_in_flight = vec![null_mut(); self.swap_chain_images.len()];
+
+        self.create_render_finished_semaphores()
     }
 
 
-    fn draw_frame(&mut self) {
+    fn draw_frame(&mut self) -> Result<()> {
+        let frame = self.current_frame;
+        let command_buffer = self.command_buffers[frame];
+        let in_flight_fence = self.in_flight_fences[frame].raw();
+
         unsafe {
-            vk::vkWaitForFences(self.device, 1, &self.in_flight, vk::VK_TRUE, u64::MAX);
-            vk::vkResetFences(self.device, 1, &mut self.in_flight);
+            check(
+                vk::vkWaitForFences(self.device.raw(), 1, &in_flight_fence, vk::VK_TRUE, u64::MAX),
+                Stage::Draw,
+                "failed to wait for the in-flight fence"
+            )?;
 
             let mut image_index: u32 = 0;
-            vk::vkAcquireNextImageKHR(
-                self.device,
-                self.swap_chain, 
+            let result = vk::vkAcquireNextImageKHR(
+                self.device.raw(),
+                self.swap_chain.raw(), 
                 u64::MAX, 
-                self.image_available_semaphore, 
+                self.image_available_semaphores[frame].raw(), 
                 null_mut(), 
                 &mut image_index);
-            vk::vkResetCommandBuffer(self.command_buffer, 0);
-            self.record_command_buffer(self.command_buffer, image_index);
+
+            if result == vk::VK_ERROR_OUT_OF_DATE_KHR {
+                return self.recreate_swapchain();
+            } else if result != vk::VK_SUBOPTIMAL_KHR {
+                check(result, Stage::Draw, "failed to acquire swapchain image")?;
+            }
+
+            // the acquired image can still be in use by an older frame when images are returned out of order
+            let image_in_flight = self.images_in_flight[image_index as usize];
+            if !image_in_flight.is_null() {
+                check(
+                    vk::vkWaitForFences(self.device.raw(), 1, &image_in_flight, vk::VK_TRUE, u64::MAX),
+                    Stage::Draw,
+                    "failed to wait for the fence of the acquired image"
+                )?;
+            }
+            self.images_in_flight[image_index as usize] = in_flight_fence;
+
+            // only reset the fence once work is guaranteed to be submitted, otherwise the next wait deadlocks
+            check(vk::vkResetFences(self.device.raw(), 1, &in_flight_fence), Stage::Draw, "failed to reset the in-flight fence")?;
+            check(vk::vkResetCommandBuffer(command_buffer, 0), Stage::Draw, "failed to reset command buffer")?;
+            self.update_uniform_buffer(frame);
+            self.record_command_buffer(command_buffer, image_index, frame)?;
 
             let mut submit_info = vk::VkSubmitInfo::default();
             submit_info.sType = vk::VK_STRUCTURE_TYPE_SUBMIT_INFO;
 
-            let wait_semaphores = [self.image_available_semaphore];
+            let wait_semaphores = [self.image_available_semaphores[frame].raw()];
             let wait_stages = [vk::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT];
             submit_info.waitSemaphoreCount = 1;
             submit_info.pWaitSemaphores = wait_semaphores.as_ptr();
             submit_info.pWaitDstStageMask = wait_stages.as_ptr() as _;
             submit_info.commandBufferCount = 1;
-            submit_info.pCommandBuffers = &self.command_buffer;
+            submit_info.pCommandBuffers = &command_buffer;
 
-            let signal_semaphores = [self.render_finished_semaphore];
+            // the presentation engine releases the semaphore with the image, so it can only be reused by the next
+            // submission that renders to the same image
+            let signal_semaphores = [self.render_finished_semaphores[image_index as usize].raw()];
             submit_info.signalSemaphoreCount = 1;
             submit_info.pSignalSemaphores = signal_semaphores.as_ptr();
 
-            if vk::vkQueueSubmit(self.graphics_queue, 1, &submit_info, self.in_flight) != vk::VK_SUCCESS {
-                panic!("failed to submit to draw command buffer");
-            }
+            check(
+                vk::vkQueueSubmit(self.graphics_queue, 1, &submit_info, in_flight_fence),
+                Stage::Draw,
+                "failed to submit draw command buffer"
+            )?;
 
             let mut present_info = vk::VkPresentInfoKHR::default();
             present_info.sType = vk::VK_STRUCTURE_TYPE_PRESENT_INFO_KHR;
             present_info.waitSemaphoreCount = 1;
             present_info.pWaitSemaphores = signal_semaphores.as_ptr();
 
-            let swap_chains = [self.swap_chain];
+            let swap_chains = [self.swap_chain.raw()];
             present_info.swapchainCount = 1;
             present_info.pSwapchains = swap_chains.as_ptr();
             present_info.pImageIndices = &image_index;
             present_info.pResults = null_mut();
 
-            vk::vkQueuePresentKHR(self.present_queue, &present_info);
+            let result = vk::vkQueuePresentKHR(self.present_queue, &present_info);
+            if result == vk::VK_ERROR_OUT_OF_DATE_KHR || result == vk::VK_SUBOPTIMAL_KHR || self.framebuffer_resized {
+                self.framebuffer_resized = false;
+                self.recreate_swapchain()?;
+            } else {
+                check(result, Stage::Present, "failed to present swapchain image")?;
+            }
+        }
+
+        self.current_frame = (self.current_frame + 1) % self.frames_in_flight();
+
+        Ok(())
+    }
+
+
+    fn draw_offscreen(&mut self) -> Result<Vec<u8>> {
+        let command_buffer = self.command_buffers[0];
+        let fence = self.in_flight_fences[0].raw();
+
+        unsafe {
+            check(vk::vkResetFences(self.device.raw(), 1, &fence), Stage::Draw, "failed to reset fence")?;
+            self.update_uniform_buffer(0);
+            self.record_command_buffer(command_buffer, 0, 0)?;
+
+            let mut submit_info = vk::VkSubmitInfo::default();
+            submit_info.sType = vk::VK_STRUCTURE_TYPE_SUBMIT_INFO;
+            submit_info.commandBufferCount = 1;
+            submit_info.pCommandBuffers = &command_buffer;
+
+            check(
+                vk::vkQueueSubmit(self.graphics_queue, 1, &submit_info, fence),
+                Stage::Draw,
+                "failed to submit offscreen command buffer"
+            )?;
+            check(
+                vk::vkWaitForFences(self.device.raw(), 1, &fence, vk::VK_TRUE, u64::MAX),
+                Stage::Draw,
+                "failed to wait for the offscreen frame"
+            )?;
+        }
+
+        self.read_offscreen_image()
+    }
+
+
+    fn read_offscreen_image(&self) -> Result<Vec<u8>> {
+        let extent = self.swap_chain_extent;
+        let size = extent.width as vk::VkDeviceSize * extent.height as vk::VkDeviceSize * 4;
+        let (buffer, buffer_memory) = self.create_buffer(
+            size,
+            vk::VK_BUFFER_USAGE_TRANSFER_DST_BIT as _,
+            (vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::VK_MEMORY_PROPERTY_HOST_COHERENT_BIT) as _
+        )?;
+
+        let command_buffer = self.begin_single_time_commands(QueueRole::Graphics)?;
+
+        let mut region = vk::VkBufferImageCopy::default();
+        region.bufferOffset = 0;
+        region.bufferRowLength = 0;
+        region.bufferImageHeight = 0;
+        region.imageSubresource.aspectMask = vk::VK_IMAGE_ASPECT_COLOR_BIT as _;
+        region.imageSubresource.mipLevel = 0;
+        region.imageSubresource.baseArrayLayer = 0;
+        region.imageSubresource.layerCount = 1;
+        region.imageOffset = vk::VkOffset3D{x: 0, y: 0, z: 0};
+        region.imageExtent = vk::VkExtent3D{width: extent.width, height: extent.height, depth: 1};
+
+        let mut barrier = vk::VkBufferMemoryBarrier::default();
+        barrier.sType = vk::VK_STRUCTURE_TYPE_BUFFER_MEMORY_BARRIER;
+        barrier.srcAccessMask = vk::VK_ACCESS_TRANSFER_WRITE_BIT as _;
+        barrier.dstAccessMask = vk::VK_ACCESS_HOST_READ_BIT as _;
+        barrier.srcQueueFamilyIndex = vk::VK_QUEUE_FAMILY_IGNORED as _;
+        barrier.dstQueueFamilyIndex = vk::VK_QUEUE_FAMILY_IGNORED as _;
+        barrier.buffer = buffer.raw();
+        barrier.offset = 0;
+        barrier.size = vk::VK_WHOLE_SIZE as _;
+
+        unsafe {
+            vk::vkCmdCopyImageToBuffer(
+                command_buffer,
+                self.swap_chain_images[0],
+                vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL,
+                buffer.raw(),
+                1,
+                &region
+            );
+            vk::vkCmdPipelineBarrier(
+                command_buffer,
+                vk::VK_PIPELINE_STAGE_TRANSFER_BIT as _,
+                vk::VK_PIPELINE_STAGE_HOST_BIT as _,
+                0,
+                0, null(),
+                1, &barrier,
+                0, null()
+            );
+        }
+
+        self.end_single_time_commands(QueueRole::Graphics, command_buffer)?;
+
+        let mut pixels = vec![0u8; size as usize];
+        unsafe {
+            let mut data: *mut std::ffi::c_void = null_mut();
+            check(
+                vk::vkMapMemory(self.device.raw(), buffer_memory.raw(), 0, size, 0, &mut data),
+                Stage::Readback,
+                "failed to map readback buffer"
+            )?;
+            std::ptr::copy_nonoverlapping(data as *const u8, pixels.as_mut_ptr(), pixels.len());
+            vk::vkUnmapMemory(self.device.raw(), buffer_memory.raw());
         }
+
+        Ok(pixels)
     }
 
 
-    fn create_sync_objects(&mut self) {
+    fn create_sync_objects(&mut self) -> Result<()> {
         let mut semaphore_create_info = vk::VkSemaphoreCreateInfo::default();
         semaphore_create_info.sType = vk::VK_STRUCTURE_TYPE_SEMAPHORE_CREATE_INFO;
         let mut fence_create_info = vk::VkFenceCreateInfo::default();
         fence_create_info.sType = vk::VK_STRUCTURE_TYPE_FENCE_CREATE_INFO;
         fence_create_info.flags = vk::VK_FENCE_CREATE_SIGNALED_BIT as _;
 
-        if unsafe {
-            vk::vkCreateSemaphore(self.device, &semaphore_create_info, null(), &mut self.image_available_semaphore) |
-            vk::vkCreateSemaphore(self.device, &semaphore_create_info, null(), &mut self.render_finished_semaphore) |
-            vk::vkCreateFence(self.device, &fence_create_info, null(), &mut self.in_flight)
-        } != vk::VK_SUCCESS {
-            panic!("failed to create synchronization objects");
+        self.images_in_flight = vec![null_mut(); self.swap_chain_images.len()];
+
+        for _ in 0..self.frames_in_flight() {
+            unsafe {
+                let mut semaphore = vk::VkSemaphore::default();
+                check(
+                    vk::vkCreateSemaphore(self.device.raw(), &semaphore_create_info, null(), &mut semaphore),
+                    Stage::SyncObjects,
+                    "failed to create image available semaphore"
+                )?;
+                self.image_available_semaphores.push(Semaphore::new(self.device.raw(), semaphore));
+
+                let mut fence = vk::VkFence::default();
+                check(
+                    vk::vkCreateFence(self.device.raw(), &fence_create_info, null(), &mut fence),
+                    Stage::SyncObjects,
+                    "failed to create in-flight fence"
+                )?;
+                self.in_flight_fences.push(Fence::new(self.device.raw(), fence));
+            }
         }
+
+        self.create_render_finished_semaphores()
     }
 
 
-    fn record_command_buffer(&self, command_buffer: vk::VkCommandBuffer, image_index: u32) {
+    /// One semaphore per swapchain image. Existing ones are kept when the swapchain is recreated, since a present
+    /// of the old swapchain can still wait on them.
+    fn create_render_finished_semaphores(&mut self) -> Result<()> {
+        let mut semaphore_create_info = vk::VkSemaphoreCreateInfo::default();
+        semaphore_create_info.sType = vk::VK_STRUCTURE_TYPE_SEMAPHORE_CREATE_INFO;
+
+        while self.render_finished_semaphores.len() < self.swap_chain_images.len() {
+            let mut semaphore = vk::VkSemaphore::default();
+            check(
+                unsafe{vk::vkCreateSemaphore(self.device.raw(), &semaphore_create_info, null(), &mut semaphore)},
+                Stage::SyncObjects,
+                "failed to create render finished semaphore"
+            )?;
+            self.render_finished_semaphores.push(Semaphore::new(self.device.raw(), semaphore));
+        }
+
+        Ok(())
+    }
+
+
+    fn frames_in_flight(&self) -> usize {
+        if self.requested_frames_in_flight == 0 {DEFAULT_FRAMES_IN_FLIGHT} else {self.requested_frames_in_flight}
+    }
+
+
+    /// Records drawing into the framebuffer of `image_index`, reading uniforms from the buffer of `frame`.
+    fn record_command_buffer(&self, command_buffer: vk::VkCommandBuffer, image_index: u32, frame: usize) -> Result<()> {
         let mut begin_info = vk::VkCommandBufferBeginInfo::default();
         begin_info.sType = vk::VK_STRUCTURE_TYPE_COMMAND_BUFFER_BEGIN_INFO;
         begin_info.flags = 0;
         begin_info.pInheritanceInfo = null();
 
-        if unsafe{vk::vkBeginCommandBuffer(command_buffer, &begin_info)} != vk::VK_SUCCESS {
-            panic!("failed to begin recording command buffer");
-        }
+        check(
+            unsafe{vk::vkBeginCommandBuffer(command_buffer, &begin_info)},
+            Stage::CommandBuffers,
+            "failed to begin recording command buffer"
+        )?;
 
         let mut render_pass_info = vk::VkRenderPassBeginInfo::default();
         render_pass_info.sType = vk::VK_STRUCTURE_TYPE_RENDER_PASS_BEGIN_INFO;
-        render_pass_info.renderPass = self.render_pass;
-        render_pass_info.framebuffer = self.swap_chain_framebuffers[image_index as usize];
+        render_pass_info.renderPass = self.render_pass.raw();
+        render_pass_info.framebuffer = self.swap_chain_framebuffers[image_index as usize].raw();
         render_pass_info.renderArea.offset = vk::VkOffset2D{x: 0, y: 0};
         render_pass_info.renderArea.extent = self.swap_chain_extent;
-        let clear_color = vk::VkClearValue{color: vk::VkClearColorValue{float32: [0.0, 0.0, 0.0, 1.0]}};
-        render_pass_info.clearValueCount = 1;
-        render_pass_info.pClearValues = &clear_color;
+        let clear_values = [
+            vk::VkClearValue{color: vk::VkClearColorValue{float32: [0.0, 0.0, 0.0, 1.0]}},
+            vk::VkClearValue{depthStencil: vk::VkClearDepthStencilValue{depth: 1.0, stencil: 0}}
+        ];
+        render_pass_info.clearValueCount = clear_values.len() as u32;
+        render_pass_info.pClearValues = clear_values.as_ptr();
 
         unsafe{
             vk::vkCmdBeginRenderPass(command_buffer, &render_pass_info, vk::VK_SUBPASS_CONTENTS_INLINE);
-            vk::vkCmdBindPipeline(command_buffer, vk::VK_PIPELINE_BIND_POINT_GRAPHICS, self.pipeline);
+            vk::vkCmdBindPipeline(command_buffer, vk::VK_PIPELINE_BIND_POINT_GRAPHICS, self.pipeline.raw());
 
             let mut viewport = vk::VkViewport::default();
             viewport.x = 0.0;
@@ -228,142 +828,1385 @@ impl HelloTriangleApplication {
             viewport.width = self.swap_chain_extent.width as _;
             viewport.height = self.swap_chain_extent.height as _;
             viewport.minDepth = 0.0;
-            viewport.maxDepth = 0.0; 
+            viewport.maxDepth = 1.0;
             vk::vkCmdSetViewport(command_buffer, 0, 1, &viewport);
 
             let mut scissor = vk::VkRect2D::default();
             scissor.offset = vk::VkOffset2D{x:0, y:0};
             scissor.extent = self.swap_chain_extent;
             vk::vkCmdSetScissor(command_buffer, 0, 1, &scissor);
-            vk::vkCmdDraw(command_buffer, 3, 1, 0, 0);
+
+            let vertex_buffers = [self.vertex_buffer.raw()];
+            let offsets: [vk::VkDeviceSize; 1] = [0];
+            vk::vkCmdBindVertexBuffers(command_buffer, 0, 1, vertex_buffers.as_ptr(), offsets.as_ptr());
+
+            vk::vkCmdBindDescriptorSets(
+                command_buffer,
+                vk::VK_PIPELINE_BIND_POINT_GRAPHICS,
+                self.pipeline_layout.raw(),
+                0,
+                1,
+                &self.descriptor_sets[frame],
+                0,
+                null()
+            );
+
+            // the pipeline layout was created with the ranges of the current interface
+            let draw = DrawContext::new(
+                command_buffer,
+                self.pipeline_layout.raw(),
+                &self.pipeline_interface.push_constant_ranges
+            );
+            let draw_range = DrawPushConstants::range();
+            if draw.covers(draw_range.stages, draw_range.offset, draw_range.size) {
+                draw.push_constants(draw_range.stages, draw_range.offset, &DrawPushConstants::default())?;
+            }
+            if let Some(draw_hook) = &self.draw_hook {
+                draw_hook(&draw)?;
+            }
+
+            match self.mesh.indices() {
+                Some(indices) => {
+                    vk::vkCmdBindIndexBuffer(command_buffer, self.index_buffer.raw(), 0, indices.index_type());
+                    vk::vkCmdDrawIndexed(command_buffer, self.mesh.draw_count(), 1, 0, 0, 0);
+                }
+                None => vk::vkCmdDraw(command_buffer, self.mesh.draw_count(), 1, 0, 0)
+            }
             vk::vkCmdEndRenderPass(command_buffer);
 
-            if vk::vkEndCommandBuffer(command_buffer) != vk::VK_SUCCESS {
-                panic!("failed to record command buffer");
+            check(vk::vkEndCommandBuffer(command_buffer), Stage::CommandBuffers, "failed to record command buffer")
+        }
+    }
+
+
+    /// The bindings of set 0 as reflected from the shaders, which only use descriptors the application provides.
+    fn create_descriptor_set_layout(&mut self) -> Result<()> {
+        let bindings: Vec<vk::VkDescriptorSetLayoutBinding> = self.pipeline_interface.descriptor_bindings
+            .iter()
+            .map(|binding| {
+                let mut layout_binding = vk::VkDescriptorSetLayoutBinding::default();
+                layout_binding.binding = binding.binding;
+                layout_binding.descriptorType = binding.descriptor_type;
+                layout_binding.descriptorCount = binding.count;
+                layout_binding.stageFlags = binding.stages;
+                layout_binding.pImmutableSamplers = null();
+
+                layout_binding
+            })
+            .collect();
+
+        let mut layout_info = vk::VkDescriptorSetLayoutCreateInfo::default();
+        layout_info.sType = vk::VK_STRUCTURE_TYPE_DESCRIPTOR_SET_LAYOUT_CREATE_INFO;
+        layout_info.bindingCount = bindings.len() as u32;
+        layout_info.pBindings = bindings.as_ptr();
+
+        let mut descriptor_set_layout = vk::VkDescriptorSetLayout::default();
+        check(
+            unsafe{vk::vkCreateDescriptorSetLayout(self.device.raw(), &layout_info, null(), &mut descriptor_set_layout)},
+            Stage::Descriptors,
+            "failed to create descriptor set layout"
+        )?;
+        self.descriptor_set_layout = DescriptorSetLayout::new(self.device.raw(), descriptor_set_layout);
+
+        Ok(())
+    }
+
+
+    fn create_descriptor_pool(&mut self) -> Result<()> {
+        let pool_sizes: Vec<vk::VkDescriptorPoolSize> = PROVIDED_DESCRIPTORS
+            .iter()
+            .map(|&(_, descriptor_type)| {
+                let mut pool_size = vk::VkDescriptorPoolSize::default();
+                pool_size.type_ = descriptor_type;
+                pool_size.descriptorCount = self.frames_in_flight() as u32;
+
+                pool_size
+            })
+            .collect();
+
+        let mut pool_info = vk::VkDescriptorPoolCreateInfo::default();
+        pool_info.sType = vk::VK_STRUCTURE_TYPE_DESCRIPTOR_POOL_CREATE_INFO;
+        pool_info.poolSizeCount = pool_sizes.len() as u32;
+        pool_info.pPoolSizes = pool_sizes.as_ptr();
+        pool_info.maxSets = self.frames_in_flight() as u32;
+
+        let mut descriptor_pool = vk::VkDescriptorPool::default();
+        check(
+            unsafe{vk::vkCreateDescriptorPool(self.device.raw(), &pool_info, null(), &mut descriptor_pool)},
+            Stage::Descriptors,
+            "failed to create descriptor pool"
+        )?;
+        self.descriptor_pool = DescriptorPool::new(self.device.raw(), descriptor_pool);
+
+        Ok(())
+    }
+
+
+    /// One set per frame in flight, each pointing at that frame's uniform buffer and the shared texture. Only the
+    /// bindings the shaders declare are written.
+    fn create_descriptor_sets(&mut self) -> Result<()> {
+        let layouts = vec![self.descriptor_set_layout.raw(); self.frames_in_flight()];
+
+        let mut alloc_info = vk::VkDescriptorSetAllocateInfo::default();
+        alloc_info.sType = vk::VK_STRUCTURE_TYPE_DESCRIPTOR_SET_ALLOCATE_INFO;
+        alloc_info.descriptorPool = self.descriptor_pool.raw();
+        alloc_info.descriptorSetCount = layouts.len() as u32;
+        alloc_info.pSetLayouts = layouts.as_ptr();
+
+        self.descriptor_sets.resize(self.frames_in_flight(), vk::VkDescriptorSet::default());
+        check(
+            unsafe{vk::vkAllocateDescriptorSets(self.device.raw(), &alloc_info, self.descriptor_sets.as_mut_ptr())},
+            Stage::Descriptors,
+            "failed to allocate descriptor sets"
+        )?;
+
+        for (descriptor_set, uniform_buffer) in self.descriptor_sets.iter().zip(&self.uniform_buffers) {
+            let mut buffer_info = vk::VkDescriptorBufferInfo::default();
+            buffer_info.buffer = uniform_buffer.raw();
+            buffer_info.offset = 0;
+            buffer_info.range = std::mem::size_of::<UniformBufferObject>() as _;
+
+            let mut image_info = vk::VkDescriptorImageInfo::default();
+            image_info.imageLayout = vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL;
+            image_info.imageView = self.texture_image_view.raw();
+            image_info.sampler = self.texture_sampler.raw();
+
+            let descriptor_writes: Vec<vk::VkWriteDescriptorSet> = self.pipeline_interface.descriptor_bindings
+                .iter()
+                .map(|binding| {
+                    let mut descriptor_write = vk::VkWriteDescriptorSet::default();
+                    descriptor_write.sType = vk::VK_STRUCTURE_TYPE_WRITE_DESCRIPTOR_SET;
+                    descriptor_write.dstSet = *descriptor_set;
+                    descriptor_write.dstBinding = binding.binding;
+                    descriptor_write.dstArrayElement = 0;
+                    descriptor_write.descriptorType = binding.descriptor_type;
+                    descriptor_write.descriptorCount = 1;
+                    if binding.descriptor_type == vk::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER {
+                        descriptor_write.pBufferInfo = &buffer_info;
+                    } else {
+                        descriptor_write.pImageInfo = &image_info;
+                    }
+
+                    descriptor_write
+                })
+                .collect();
+
+            unsafe{vk::vkUpdateDescriptorSets(
+                self.device.raw(),
+                descriptor_writes.len() as u32,
+                descriptor_writes.as_ptr(),
+                0,
+                null()
+            )};
+        }
+
+        Ok(())
+    }
+
+
+    fn create_command_buffers(&mut self) -> Result<()> {
+        self.command_buffers.resize(self.frames_in_flight(), vk::VkCommandBuffer::default());
+
+        let mut alloc_info = vk::VkCommandBufferAllocateInfo::default();
+        alloc_info.sType = vk::VK_STRUCTURE_TYPE_COMMAND_BUFFER_ALLOCATE_INFO;
+        alloc_info.commandPool = self.command_pool.raw();
+        alloc_info.level = vk::VK_COMMAND_BUFFER_LEVEL_PRIMARY;
+        alloc_info.commandBufferCount = self.command_buffers.len() as u32;
+
+        check(
+            unsafe{vk::vkAllocateCommandBuffers(self.device.raw(), &alloc_info, self.command_buffers.as_mut_ptr())},
+            Stage::CommandBuffers,
+            "failed to allocate command buffers"
+        )
+    }
+
+
+    fn create_vertex_buffer(&mut self) -> Result<()> {
+        if self.mesh.vertices().is_empty() {
+            return unsupported(Stage::Memory, "the mesh has no vertices");
+        }
+
+        let (vertex_buffer, vertex_buffer_memory) = self.create_device_local_buffer(
+            self.mesh.vertex_bytes(),
+            vk::VK_BUFFER_USAGE_VERTEX_BUFFER_BIT as _
+        )?;
+        self.vertex_buffer = vertex_buffer;
+        self.vertex_buffer_memory = vertex_buffer_memory;
+
+        Ok(())
+    }
+
+
+    /// Does nothing for meshes without indices. `Mesh` checks that the indices are in range.
+    fn create_index_buffer(&mut self) -> Result<()> {
+        let Some(indices) = self.mesh.indices() else {return Ok(())};
+
+        let (index_buffer, index_buffer_memory) = self.create_device_local_buffer(
+            indices.as_bytes(),
+            vk::VK_BUFFER_USAGE_INDEX_BUFFER_BIT as _
+        )?;
+        self.index_buffer = index_buffer;
+        self.index_buffer_memory = index_buffer_memory;
+
+        Ok(())
+    }
+
+
+    /// Uploads `self.texture` with a full mip chain into a device-local image through a staging buffer and
+    /// leaves it ready for sampling. The chain is blitted on the gpu when the texture format supports linear
+    /// filtering, and generated on the cpu otherwise.
+    fn create_texture_image(&mut self) -> Result<()> {
+        let TextureData {width, height, pixels} = &self.texture;
+        let (width, height) = (*width, *height);
+        if width == 0 || height == 0 || pixels.len() != width as usize * height as usize * 4 {
+            return unsupported(Stage::Textures, "texture pixels do not match its extent");
+        }
+
+        let mip_levels = self.texture.mip_levels();
+        // the blits filter linearly, which the format has to support on top of blitting
+        let features = self.optimal_tiling_features(TEXTURE_FORMAT);
+        let blit = (vk::VK_FORMAT_FEATURE_BLIT_SRC_BIT | vk::VK_FORMAT_FEATURE_BLIT_DST_BIT) as vk::VkFormatFeatureFlags;
+        let blit_mipmaps = features & blit == blit && supports_linear_filter(features);
+        let levels = if blit_mipmaps {vec![self.texture.clone()]} else {self.texture.mip_chain()};
+
+        let size: usize = levels.iter().map(TextureData::size).sum();
+        let (staging_buffer, staging_buffer_memory) = self.create_buffer(
+            size as vk::VkDeviceSize,
+            vk::VK_BUFFER_USAGE_TRANSFER_SRC_BIT as _,
+            (vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::VK_MEMORY_PROPERTY_HOST_COHERENT_BIT) as _
+        )?;
+
+        unsafe {
+            let mut mapped: *mut std::ffi::c_void = null_mut();
+            check(
+                vk::vkMapMemory(self.device.raw(), staging_buffer_memory.raw(), 0, size as _, 0, &mut mapped),
+                Stage::Textures,
+                "failed to map texture staging buffer"
+            )?;
+            let mut offset = 0;
+            for level in &levels {
+                std::ptr::copy_nonoverlapping(level.pixels.as_ptr(), (mapped as *mut u8).add(offset), level.size());
+                offset += level.size();
+            }
+            vk::vkUnmapMemory(self.device.raw(), staging_buffer_memory.raw());
+        }
+
+        let (image, image_memory) = self.create_image(
+            width,
+            height,
+            mip_levels,
+            vk::VK_SAMPLE_COUNT_1_BIT,
+            TEXTURE_FORMAT,
+            vk::VK_IMAGE_TILING_OPTIMAL,
+            (vk::VK_IMAGE_USAGE_TRANSFER_SRC_BIT | vk::VK_IMAGE_USAGE_TRANSFER_DST_BIT | vk::VK_IMAGE_USAGE_SAMPLED_BIT) as _,
+            vk::VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as _
+        )?;
+
+        self.transition_image_layout(
+            &image,
+            mip_levels,
+            vk::VK_IMAGE_LAYOUT_UNDEFINED,
+            vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL
+        )?;
+
+        let mut offset = 0;
+        for (mip_level, level) in levels.iter().enumerate() {
+            self.copy_buffer_to_image(&staging_buffer, &image, offset as _, mip_level as u32, level.width, level.height)?;
+            offset += level.size();
+        }
+        // blitting and the final transition need the graphics queue
+        self.transfer_to_graphics_family(
+            Transferred::Image(&image, mip_levels),
+            (vk::VK_ACCESS_TRANSFER_READ_BIT | vk::VK_ACCESS_TRANSFER_WRITE_BIT) as _,
+            vk::VK_PIPELINE_STAGE_TRANSFER_BIT as _
+        )?;
+
+        if blit_mipmaps {
+            self.generate_mipmaps(&image, width, height, mip_levels)?;
+        } else {
+            self.transition_image_layout(
+                &image,
+                mip_levels,
+                vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+                vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL
+            )?;
+        }
+
+        self.texture_image = image;
+        self.texture_image_memory = image_memory;
+        self.texture_mip_levels = mip_levels;
+
+        Ok(())
+    }
+
+
+    fn optimal_tiling_features(&self, format: vk::VkFormat) -> vk::VkFormatFeatureFlags {
+        let mut format_properties = vk::VkFormatProperties::default();
+        unsafe{vk::vkGetPhysicalDeviceFormatProperties(self.physical_device, format, &mut format_properties)};
+
+        format_properties.optimalTilingFeatures
+    }
+
+
+    /// Fills mip levels 1.. of `image` by repeatedly blitting each level into the next one. Expects every level
+    /// in the transfer destination layout with level 0 filled in, and leaves them all ready for sampling.
+    fn generate_mipmaps(&self, image: &Image, width: u32, height: u32, mip_levels: u32) -> Result<()> {
+        let command_buffer = self.begin_single_time_commands(QueueRole::Graphics)?;
+
+        let mut barrier = vk::VkImageMemoryBarrier::default();
+        barrier.sType = vk::VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER;
+        barrier.image = image.raw();
+        barrier.srcQueueFamilyIndex = vk::VK_QUEUE_FAMILY_IGNORED as _;
+        barrier.dstQueueFamilyIndex = vk::VK_QUEUE_FAMILY_IGNORED as _;
+        barrier.subresourceRange.aspectMask = vk::VK_IMAGE_ASPECT_COLOR_BIT as _;
+        barrier.subresourceRange.baseArrayLayer = 0;
+        barrier.subresourceRange.layerCount = 1;
+        barrier.subresourceRange.levelCount = 1;
+
+        let mut mip_width = width as i32;
+        let mut mip_height = height as i32;
+
+        unsafe {
+            for level in 1..mip_levels {
+                // the previous level becomes the blit source
+                barrier.subresourceRange.baseMipLevel = level - 1;
+                barrier.oldLayout = vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL;
+                barrier.newLayout = vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL;
+                barrier.srcAccessMask = vk::VK_ACCESS_TRANSFER_WRITE_BIT as _;
+                barrier.dstAccessMask = vk::VK_ACCESS_TRANSFER_READ_BIT as _;
+                vk::vkCmdPipelineBarrier(
+                    command_buffer,
+                    vk::VK_PIPELINE_STAGE_TRANSFER_BIT as _,
+                    vk::VK_PIPELINE_STAGE_TRANSFER_BIT as _,
+                    0,
+                    0,
+                    null(),
+                    0,
+                    null(),
+                    1,
+                    &barrier
+                );
+
+                let next_width = (mip_width / 2).max(1);
+                let next_height = (mip_height / 2).max(1);
+
+                let mut blit = vk::VkImageBlit::default();
+                blit.srcOffsets[0] = vk::VkOffset3D{x: 0, y: 0, z: 0};
+                blit.srcOffsets[1] = vk::VkOffset3D{x: mip_width, y: mip_height, z: 1};
+                blit.srcSubresource.aspectMask = vk::VK_IMAGE_ASPECT_COLOR_BIT as _;
+                blit.srcSubresource.mipLevel = level - 1;
+                blit.srcSubresource.baseArrayLayer = 0;
+                blit.srcSubresource.layerCount = 1;
+                blit.dstOffsets[0] = vk::VkOffset3D{x: 0, y: 0, z: 0};
+                blit.dstOffsets[1] = vk::VkOffset3D{x: next_width, y: next_height, z: 1};
+                blit.dstSubresource.aspectMask = vk::VK_IMAGE_ASPECT_COLOR_BIT as _;
+                blit.dstSubresource.mipLevel = level;
+                blit.dstSubresource.baseArrayLayer = 0;
+                blit.dstSubresource.layerCount = 1;
+                vk::vkCmdBlitImage(
+                    command_buffer,
+                    image.raw(),
+                    vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL,
+                    image.raw(),
+                    vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+                    1,
+                    &blit,
+                    vk::VK_FILTER_LINEAR
+                );
+
+                // the source level is done
+                barrier.oldLayout = vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL;
+                barrier.newLayout = vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL;
+                barrier.srcAccessMask = vk::VK_ACCESS_TRANSFER_READ_BIT as _;
+                barrier.dstAccessMask = vk::VK_ACCESS_SHADER_READ_BIT as _;
+                vk::vkCmdPipelineBarrier(
+                    command_buffer,
+                    vk::VK_PIPELINE_STAGE_TRANSFER_BIT as _,
+                    vk::VK_PIPELINE_STAGE_FRAGMENT_SHADER_BIT as _,
+                    0,
+                    0,
+                    null(),
+                    0,
+                    null(),
+                    1,
+                    &barrier
+                );
+
+                mip_width = next_width;
+                mip_height = next_height;
             }
+
+            // the last level was only ever blitted into
+            barrier.subresourceRange.baseMipLevel = mip_levels - 1;
+            barrier.oldLayout = vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL;
+            barrier.newLayout = vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL;
+            barrier.srcAccessMask = vk::VK_ACCESS_TRANSFER_WRITE_BIT as _;
+            barrier.dstAccessMask = vk::VK_ACCESS_SHADER_READ_BIT as _;
+            vk::vkCmdPipelineBarrier(
+                command_buffer,
+                vk::VK_PIPELINE_STAGE_TRANSFER_BIT as _,
+                vk::VK_PIPELINE_STAGE_FRAGMENT_SHADER_BIT as _,
+                0,
+                0,
+                null(),
+                0,
+                null(),
+                1,
+                &barrier
+            );
         }
+
+        self.end_single_time_commands(QueueRole::Graphics, command_buffer)
+    }
+
+
+    fn create_texture_image_view(&mut self) -> Result<()> {
+        self.texture_image_view = self.create_image_view(
+            self.texture_image.raw(),
+            TEXTURE_FORMAT,
+            vk::VK_IMAGE_ASPECT_COLOR_BIT as _,
+            self.texture_mip_levels,
+            Stage::Textures
+        )?;
+
+        Ok(())
+    }
+
+
+    fn create_texture_sampler(&mut self) -> Result<()> {
+        let mut device_properties = vk::VkPhysicalDeviceProperties::default();
+        unsafe{vk::vkGetPhysicalDeviceProperties(self.physical_device, &mut device_properties)};
+        let max_lod_bias = device_properties.limits.maxSamplerLodBias;
+
+        let max_lod = self.texture_mip_levels as f32;
+        let lod = self.sampler_lod;
+        // linear filtering between texels and between mip levels both need the format to support it
+        let linear = supports_linear_filter(self.optimal_tiling_features(TEXTURE_FORMAT));
+        let filter = if linear {vk::VK_FILTER_LINEAR} else {vk::VK_FILTER_NEAREST};
+
+        let mut sampler_info = vk::VkSamplerCreateInfo::default();
+        sampler_info.sType = vk::VK_STRUCTURE_TYPE_SAMPLER_CREATE_INFO;
+        sampler_info.magFilter = filter;
+        sampler_info.minFilter = filter;
+        sampler_info.addressModeU = vk::VK_SAMPLER_ADDRESS_MODE_REPEAT;
+        sampler_info.addressModeV = vk::VK_SAMPLER_ADDRESS_MODE_REPEAT;
+        sampler_info.addressModeW = vk::VK_SAMPLER_ADDRESS_MODE_REPEAT;
+        // optional, so it is only used on devices that have it
+        let anisotropy = self.enabled_features.core.samplerAnisotropy == vk::VK_TRUE;
+        sampler_info.anisotropyEnable = if anisotropy {vk::VK_TRUE} else {vk::VK_FALSE};
+        sampler_info.maxAnisotropy = if anisotropy {device_properties.limits.maxSamplerAnisotropy} else {1.0};
+        sampler_info.borderColor = vk::VK_BORDER_COLOR_INT_OPAQUE_BLACK;
+        sampler_info.unnormalizedCoordinates = vk::VK_FALSE;
+        sampler_info.compareEnable = vk::VK_FALSE;
+        sampler_info.compareOp = vk::VK_COMPARE_OP_ALWAYS;
+        sampler_info.mipmapMode = if linear {vk::VK_SAMPLER_MIPMAP_MODE_LINEAR} else {vk::VK_SAMPLER_MIPMAP_MODE_NEAREST};
+        sampler_info.mipLodBias = lod.mip_lod_bias.clamp(-max_lod_bias, max_lod_bias);
+        sampler_info.minLod = lod.min_lod.clamp(0.0, max_lod);
+        sampler_info.maxLod = lod.max_lod.unwrap_or(max_lod).clamp(sampler_info.minLod, max_lod);
+
+        let mut sampler = vk::VkSampler::default();
+        check(
+            unsafe{vk::vkCreateSampler(self.device.raw(), &sampler_info, null(), &mut sampler)},
+            Stage::Textures,
+            "failed to create texture sampler"
+        )?;
+        self.texture_sampler = Sampler::new(self.device.raw(), sampler);
+
+        Ok(())
     }
 
 
-    fn create_command_buffer(&mut self) {
+    /// Moves the first `mip_levels` levels of a color image between the layouts of a texture upload and waits for
+    /// the transition to finish. The move into the transfer destination layout happens on the transfer queue, the
+    /// move out of it on the graphics queue.
+    fn transition_image_layout(
+        &self,
+        image: &Image,
+        mip_levels: u32,
+        old_layout: vk::VkImageLayout,
+        new_layout: vk::VkImageLayout
+    ) -> Result<()> {
+        let mut barrier = vk::VkImageMemoryBarrier::default();
+        barrier.sType = vk::VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER;
+        barrier.oldLayout = old_layout;
+        barrier.newLayout = new_layout;
+        barrier.srcQueueFamilyIndex = vk::VK_QUEUE_FAMILY_IGNORED as _;
+        barrier.dstQueueFamilyIndex = vk::VK_QUEUE_FAMILY_IGNORED as _;
+        barrier.image = image.raw();
+        barrier.subresourceRange.aspectMask = vk::VK_IMAGE_ASPECT_COLOR_BIT as _;
+        barrier.subresourceRange.baseMipLevel = 0;
+        barrier.subresourceRange.levelCount = mip_levels;
+        barrier.subresourceRange.baseArrayLayer = 0;
+        barrier.subresourceRange.layerCount = 1;
+
+        let (source_stage, destination_stage, role) = if old_layout == vk::VK_IMAGE_LAYOUT_UNDEFINED
+            && new_layout == vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL {
+            barrier.srcAccessMask = 0;
+            barrier.dstAccessMask = vk::VK_ACCESS_TRANSFER_WRITE_BIT as _;
+            (vk::VK_PIPELINE_STAGE_TOP_OF_PIPE_BIT, vk::VK_PIPELINE_STAGE_TRANSFER_BIT, QueueRole::Transfer)
+        } else if old_layout == vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL
+            && new_layout == vk::VK_IMAGE_LAYOUT_SHADER_READ_ONLY_OPTIMAL {
+            barrier.srcAccessMask = vk::VK_ACCESS_TRANSFER_WRITE_BIT as _;
+            barrier.dstAccessMask = vk::VK_ACCESS_SHADER_READ_BIT as _;
+            (vk::VK_PIPELINE_STAGE_TRANSFER_BIT, vk::VK_PIPELINE_STAGE_FRAGMENT_SHADER_BIT, QueueRole::Graphics)
+        } else {
+            return unsupported(Stage::Textures, "unsupported image layout transition");
+        };
+
+        let command_buffer = self.begin_single_time_commands(role)?;
+        unsafe{vk::vkCmdPipelineBarrier(
+            command_buffer,
+            source_stage as _,
+            destination_stage as _,
+            0,
+            0,
+            null(),
+            0,
+            null(),
+            1,
+            &barrier
+        )};
+
+        self.end_single_time_commands(role, command_buffer)
+    }
+
+
+    /// Copies tightly packed pixels starting at `buffer_offset` into one mip level of `image` on the transfer queue.
+    /// The image has to be in the transfer destination layout.
+    fn copy_buffer_to_image(
+        &self,
+        buffer: &Buffer,
+        image: &Image,
+        buffer_offset: vk::VkDeviceSize,
+        mip_level: u32,
+        width: u32,
+        height: u32
+    ) -> Result<()> {
+        let command_buffer = self.begin_single_time_commands(QueueRole::Transfer)?;
+
+        let mut region = vk::VkBufferImageCopy::default();
+        region.bufferOffset = buffer_offset;
+        region.bufferRowLength = 0;
+        region.bufferImageHeight = 0;
+        region.imageSubresource.aspectMask = vk::VK_IMAGE_ASPECT_COLOR_BIT as _;
+        region.imageSubresource.mipLevel = mip_level;
+        region.imageSubresource.baseArrayLayer = 0;
+        region.imageSubresource.layerCount = 1;
+        region.imageOffset = vk::VkOffset3D{x: 0, y: 0, z: 0};
+        region.imageExtent = vk::VkExtent3D{width, height, depth: 1};
+
+        unsafe{vk::vkCmdCopyBufferToImage(
+            command_buffer,
+            buffer.raw(),
+            image.raw(),
+            vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL,
+            1,
+            &region
+        )};
+
+        self.end_single_time_commands(QueueRole::Transfer, command_buffer)
+    }
+
+
+    /// One host-visible buffer per frame in flight, so a frame's uniforms can be written while the previous
+    /// frame still reads its own. The buffers stay mapped for the lifetime of the application.
+    fn create_uniform_buffers(&mut self) -> Result<()> {
+        let size = std::mem::size_of::<UniformBufferObject>() as vk::VkDeviceSize;
+
+        for _ in 0..self.frames_in_flight() {
+            let (buffer, buffer_memory) = self.create_buffer(
+                size,
+                vk::VK_BUFFER_USAGE_UNIFORM_BUFFER_BIT as _,
+                (vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::VK_MEMORY_PROPERTY_HOST_COHERENT_BIT) as _
+            )?;
+
+            let mut mapped: *mut std::ffi::c_void = null_mut();
+            check(
+                unsafe{vk::vkMapMemory(self.device.raw(), buffer_memory.raw(), 0, size, 0, &mut mapped)},
+                Stage::Memory,
+                "failed to map uniform buffer"
+            )?;
+
+            self.uniform_buffers.push(buffer);
+            self.uniform_buffers_memory.push(buffer_memory);
+            self.uniform_buffers_mapped.push(mapped);
+        }
+
+        Ok(())
+    }
+
+
+    /// Writes the uniforms for `frame`. Its buffer must no longer be read by the gpu, which holds once the
+    /// frame's in-flight fence has been waited on.
+    fn update_uniform_buffer(&self, frame: usize) {
+        // headless renders are compared against reference images, so they always show the first frame
+        let time = match self.start_time {
+            Some(start_time) if !self.headless => start_time.elapsed().as_secs_f32(),
+            _ => 0.0
+        };
+
+        let ubo = UniformBufferObject::new(
+            IDENTITY,
+            IDENTITY,
+            aspect_correction(self.swap_chain_extent.width, self.swap_chain_extent.height),
+            time
+        );
+
+        unsafe{std::ptr::write(self.uniform_buffers_mapped[frame] as *mut UniformBufferObject, ubo)};
+    }
+
+
+    /// Uploads `data` into a new device-local buffer through a host-visible staging buffer.
+    /// `usage` only needs the flags for how the buffer is consumed, the transfer destination flag is added here.
+    fn create_device_local_buffer(
+        &self,
+        data: &[u8],
+        usage: vk::VkBufferUsageFlags
+    ) -> Result<(Buffer, DeviceMemory)> {
+        let size = data.len() as vk::VkDeviceSize;
+        let (staging_buffer, staging_buffer_memory) = self.create_buffer(
+            size,
+            vk::VK_BUFFER_USAGE_TRANSFER_SRC_BIT as _,
+            (vk::VK_MEMORY_PROPERTY_HOST_VISIBLE_BIT | vk::VK_MEMORY_PROPERTY_HOST_COHERENT_BIT) as _
+        )?;
+
+        unsafe {
+            let mut mapped: *mut std::ffi::c_void = null_mut();
+            check(
+                vk::vkMapMemory(self.device.raw(), staging_buffer_memory.raw(), 0, size, 0, &mut mapped),
+                Stage::Memory,
+                "failed to map staging buffer"
+            )?;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), mapped as *mut u8, data.len());
+            vk::vkUnmapMemory(self.device.raw(), staging_buffer_memory.raw());
+        }
+
+        let (buffer, buffer_memory) = self.create_buffer(
+            size,
+            vk::VK_BUFFER_USAGE_TRANSFER_DST_BIT as vk::VkBufferUsageFlags | usage,
+            vk::VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as _
+        )?;
+        self.copy_buffer(&staging_buffer, &buffer, size)?;
+        let (access, stage) = buffer_access(usage);
+        self.transfer_to_graphics_family(Transferred::Buffer(&buffer), access, stage)?;
+
+        Ok((buffer, buffer_memory))
+    }
+
+
+    /// Copies on the transfer queue.
+    fn copy_buffer(&self, src: &Buffer, dst: &Buffer, size: vk::VkDeviceSize) -> Result<()> {
+        let command_buffer = self.begin_single_time_commands(QueueRole::Transfer)?;
+
+        let mut copy_region = vk::VkBufferCopy::default();
+        copy_region.srcOffset = 0;
+        copy_region.dstOffset = 0;
+        copy_region.size = size;
+        unsafe{vk::vkCmdCopyBuffer(command_buffer, src.raw(), dst.raw(), 1, &copy_region)};
+
+        self.end_single_time_commands(QueueRole::Transfer, command_buffer)
+    }
+
+
+    /// Hands a resource written on the transfer queue over to the graphics queue family, which accesses it with
+    /// `destination_access` in `destination_stage` afterwards. Resources are created with exclusive sharing, so
+    /// this takes a release on the transfer queue and a matching acquire on the graphics queue. Does nothing when
+    /// uploads already run on the graphics family.
+    fn transfer_to_graphics_family(
+        &self,
+        resource: Transferred,
+        destination_access: vk::VkAccessFlags,
+        destination_stage: vk::VkPipelineStageFlags
+    ) -> Result<()> {
+        let indices = &self.queue_family_indices;
+        let (transfer_family, graphics_family) = (indices.transfer_family().unwrap(), indices.graphics_family.unwrap());
+        if transfer_family == graphics_family {
+            return Ok(());
+        }
+
+        let record = |command_buffer, source_access, destination_access, source_stage, destination_stage| {
+            match resource {
+                Transferred::Buffer(buffer) => {
+                    let mut barrier = vk::VkBufferMemoryBarrier::default();
+                    barrier.sType = vk::VK_STRUCTURE_TYPE_BUFFER_MEMORY_BARRIER;
+                    barrier.srcAccessMask = source_access;
+                    barrier.dstAccessMask = destination_access;
+                    barrier.srcQueueFamilyIndex = transfer_family;
+                    barrier.dstQueueFamilyIndex = graphics_family;
+                    barrier.buffer = buffer.raw();
+                    barrier.offset = 0;
+                    barrier.size = vk::VK_WHOLE_SIZE as _;
+                    unsafe{vk::vkCmdPipelineBarrier(
+                        command_buffer, source_stage, destination_stage, 0, 0, null(), 1, &barrier, 0, null()
+                    )};
+                }
+                Transferred::Image(image, mip_levels) => {
+                    // the layout stays the same, a transition would have to be repeated in both halves
+                    let mut barrier = vk::VkImageMemoryBarrier::default();
+                    barrier.sType = vk::VK_STRUCTURE_TYPE_IMAGE_MEMORY_BARRIER;
+                    barrier.srcAccessMask = source_access;
+                    barrier.dstAccessMask = destination_access;
+                    barrier.oldLayout = vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL;
+                    barrier.newLayout = vk::VK_IMAGE_LAYOUT_TRANSFER_DST_OPTIMAL;
+                    barrier.srcQueueFamilyIndex = transfer_family;
+                    barrier.dstQueueFamilyIndex = graphics_family;
+                    barrier.image = image.raw();
+                    barrier.subresourceRange.aspectMask = vk::VK_IMAGE_ASPECT_COLOR_BIT as _;
+                    barrier.subresourceRange.baseMipLevel = 0;
+                    barrier.subresourceRange.levelCount = mip_levels;
+                    barrier.subresourceRange.baseArrayLayer = 0;
+                    barrier.subresourceRange.layerCount = 1;
+                    unsafe{vk::vkCmdPipelineBarrier(
+                        command_buffer, source_stage, destination_stage, 0, 0, null(), 0, null(), 1, &barrier
+                    )};
+                }
+            }
+        };
+
+        // the release makes the transfer writes available, the access masks of the acquire make them visible
+        let command_buffer = self.begin_single_time_commands(QueueRole::Transfer)?;
+        record(
+            command_buffer,
+            vk::VK_ACCESS_TRANSFER_WRITE_BIT as _,
+            0,
+            vk::VK_PIPELINE_STAGE_TRANSFER_BIT as _,
+            vk::VK_PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT as _
+        );
+        self.end_single_time_commands(QueueRole::Transfer, command_buffer)?;
+
+        let command_buffer = self.begin_single_time_commands(QueueRole::Graphics)?;
+        record(
+            command_buffer,
+            0,
+            destination_access,
+            vk::VK_PIPELINE_STAGE_TOP_OF_PIPE_BIT as _,
+            destination_stage
+        );
+        self.end_single_time_commands(QueueRole::Graphics, command_buffer)
+    }
+
+
+    fn single_time_queue(&self, role: QueueRole) -> (vk::VkCommandPool, vk::VkQueue) {
+        match role {
+            QueueRole::Graphics => (self.command_pool.raw(), self.graphics_queue),
+            QueueRole::Transfer => (self.transfer_command_pool.raw(), self.transfer_queue)
+        }
+    }
+
+
+    fn begin_single_time_commands(&self, role: QueueRole) -> Result<vk::VkCommandBuffer> {
+        let (command_pool, _) = self.single_time_queue(role);
         let mut alloc_info = vk::VkCommandBufferAllocateInfo::default();
         alloc_info.sType = vk::VK_STRUCTURE_TYPE_COMMAND_BUFFER_ALLOCATE_INFO;
-        alloc_info.commandPool = self.command_pool;
+        alloc_info.commandPool = command_pool;
         alloc_info.level = vk::VK_COMMAND_BUFFER_LEVEL_PRIMARY;
         alloc_info.commandBufferCount = 1;
 
-        if unsafe{vk::vkAllocateCommandBuffers(self.device, &alloc_info, &mut self.command_buffer)} != vk::VK_SUCCESS {
-            panic!("failed to allocate command buffers");
+        let mut command_buffer = vk::VkCommandBuffer::default();
+        check(
+            unsafe{vk::vkAllocateCommandBuffers(self.device.raw(), &alloc_info, &mut command_buffer)},
+            Stage::CommandBuffers,
+            "failed to allocate single use command buffer"
+        )?;
+
+        let mut begin_info = vk::VkCommandBufferBeginInfo::default();
+        begin_info.sType = vk::VK_STRUCTURE_TYPE_COMMAND_BUFFER_BEGIN_INFO;
+        begin_info.flags = vk::VK_COMMAND_BUFFER_USAGE_ONE_TIME_SUBMIT_BIT as _;
+
+        let result = unsafe{vk::vkBeginCommandBuffer(command_buffer, &begin_info)};
+        if result != vk::VK_SUCCESS {
+            unsafe{vk::vkFreeCommandBuffers(self.device.raw(), command_pool, 1, &command_buffer)};
+            return Err(AppError::Vulkan {
+                stage: Stage::CommandBuffers,
+                result,
+                context: "failed to begin recording single use command buffer".to_string()
+            });
         }
+
+        Ok(command_buffer)
     }
 
 
-    fn create_command_pool(&mut self) {
-        let queue_families = self.get_queue_families(self.physical_device);
+    /// Submits a buffer from `begin_single_time_commands` to the queue of the same `role` and blocks until it has
+    /// executed. The command buffer is freed even if submission fails.
+    fn end_single_time_commands(&self, role: QueueRole, command_buffer: vk::VkCommandBuffer) -> Result<()> {
+        let (command_pool, queue) = self.single_time_queue(role);
+        let mut submit_info = vk::VkSubmitInfo::default();
+        submit_info.sType = vk::VK_STRUCTURE_TYPE_SUBMIT_INFO;
+        submit_info.commandBufferCount = 1;
+        submit_info.pCommandBuffers = &command_buffer;
+
+        let result = unsafe {
+            check(vk::vkEndCommandBuffer(command_buffer), Stage::CommandBuffers, "failed to record single use command buffer")
+                .and_then(|()| check(
+                    vk::vkQueueSubmit(queue, 1, &submit_info, null_mut()),
+                    Stage::CommandBuffers,
+                    "failed to submit single use command buffer"
+                ))
+                .and_then(|()| check(
+                    vk::vkQueueWaitIdle(queue),
+                    Stage::CommandBuffers,
+                    "failed to wait for single use command buffer"
+                ))
+        };
+
+        unsafe{vk::vkFreeCommandBuffers(self.device.raw(), command_pool, 1, &command_buffer)};
+
+        result
+    }
+
+
+    fn find_memory_type(&self, type_filter: u32, properties: vk::VkMemoryPropertyFlags) -> Result<u32> {
+        let mut memory_properties = vk::VkPhysicalDeviceMemoryProperties::default();
+        unsafe{vk::vkGetPhysicalDeviceMemoryProperties(self.physical_device, &mut memory_properties)};
+
+        for i in 0..memory_properties.memoryTypeCount {
+            if type_filter & (1 << i) != 0
+                && memory_properties.memoryTypes[i as usize].propertyFlags & properties == properties {
+                return Ok(i);
+            }
+        }
+
+        unsupported(Stage::Memory, "failed to find a suitable memory type")
+    }
+
+
+    fn create_buffer(
+        &self,
+        size: vk::VkDeviceSize,
+        usage: vk::VkBufferUsageFlags,
+        properties: vk::VkMemoryPropertyFlags
+    ) -> Result<(Buffer, DeviceMemory)> {
+        let mut buffer_info = vk::VkBufferCreateInfo::default();
+        buffer_info.sType = vk::VK_STRUCTURE_TYPE_BUFFER_CREATE_INFO;
+        buffer_info.size = size;
+        buffer_info.usage = usage;
+        buffer_info.sharingMode = vk::VK_SHARING_MODE_EXCLUSIVE;
+
+        let mut buffer = vk::VkBuffer::default();
+        check(
+            unsafe{vk::vkCreateBuffer(self.device.raw(), &buffer_info, null(), &mut buffer)},
+            Stage::Memory,
+            "failed to create buffer"
+        )?;
+        let buffer = Buffer::new(self.device.raw(), buffer);
+
+        let mut memory_requirements = vk::VkMemoryRequirements::default();
+        unsafe{vk::vkGetBufferMemoryRequirements(self.device.raw(), buffer.raw(), &mut memory_requirements)};
+
+        let mut alloc_info = vk::VkMemoryAllocateInfo::default();
+        alloc_info.sType = vk::VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO;
+        alloc_info.allocationSize = memory_requirements.size;
+        alloc_info.memoryTypeIndex = self.find_memory_type(memory_requirements.memoryTypeBits, properties)?;
+
+        let mut buffer_memory = vk::VkDeviceMemory::default();
+        check(
+            unsafe{vk::vkAllocateMemory(self.device.raw(), &alloc_info, null(), &mut buffer_memory)},
+            Stage::Memory,
+            "failed to allocate buffer memory"
+        )?;
+        let buffer_memory = DeviceMemory::new(self.device.raw(), buffer_memory);
+
+        check(
+            unsafe{vk::vkBindBufferMemory(self.device.raw(), buffer.raw(), buffer_memory.raw(), 0)},
+            Stage::Memory,
+            "failed to bind buffer memory"
+        )?;
+
+        Ok((buffer, buffer_memory))
+    }
+
+
+    fn create_image(
+        &self,
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+        samples: vk::VkSampleCountFlagBits,
+        format: vk::VkFormat,
+        tiling: vk::VkImageTiling,
+        usage: vk::VkImageUsageFlags,
+        properties: vk::VkMemoryPropertyFlags
+    ) -> Result<(Image, DeviceMemory)> {
+        let mut image_info = vk::VkImageCreateInfo::default();
+        image_info.sType = vk::VK_STRUCTURE_TYPE_IMAGE_CREATE_INFO;
+        image_info.imageType = vk::VK_IMAGE_TYPE_2D;
+        image_info.extent = vk::VkExtent3D{width, height, depth: 1};
+        image_info.mipLevels = mip_levels;
+        image_info.arrayLayers = 1;
+        image_info.format = format;
+        image_info.tiling = tiling;
+        image_info.initialLayout = vk::VK_IMAGE_LAYOUT_UNDEFINED;
+        image_info.usage = usage;
+        image_info.samples = samples;
+        image_info.sharingMode = vk::VK_SHARING_MODE_EXCLUSIVE;
+
+        let mut image = vk::VkImage::default();
+        check(
+            unsafe{vk::vkCreateImage(self.device.raw(), &image_info, null(), &mut image)},
+            Stage::Memory,
+            "failed to create image"
+        )?;
+        let image = Image::new(self.device.raw(), image);
+
+        let mut memory_requirements = vk::VkMemoryRequirements::default();
+        unsafe{vk::vkGetImageMemoryRequirements(self.device.raw(), image.raw(), &mut memory_requirements)};
+
+        let mut alloc_info = vk::VkMemoryAllocateInfo::default();
+        alloc_info.sType = vk::VK_STRUCTURE_TYPE_MEMORY_ALLOCATE_INFO;
+        alloc_info.allocationSize = memory_requirements.size;
+        alloc_info.memoryTypeIndex = self.find_memory_type(memory_requirements.memoryTypeBits, properties)?;
+
+        let mut image_memory = vk::VkDeviceMemory::default();
+        check(
+            unsafe{vk::vkAllocateMemory(self.device.raw(), &alloc_info, null(), &mut image_memory)},
+            Stage::Memory,
+            "failed to allocate image memory"
+        )?;
+        let image_memory = DeviceMemory::new(self.device.raw(), image_memory);
+
+        check(
+            unsafe{vk::vkBindImageMemory(self.device.raw(), image.raw(), image_memory.raw(), 0)},
+            Stage::Memory,
+            "failed to bind image memory"
+        )?;
+
+        Ok((image, image_memory))
+    }
+
+
+    fn create_command_pool(&mut self) -> Result<()> {
+        let queue_families = self.queue_family_indices;
 
         let mut pool_info = vk::VkCommandPoolCreateInfo::default();
         pool_info.sType = vk::VK_STRUCTURE_TYPE_COMMAND_POOL_CREATE_INFO;
         pool_info.flags = vk::VK_COMMAND_POOL_CREATE_RESET_COMMAND_BUFFER_BIT as _;
         pool_info.queueFamilyIndex = queue_families.graphics_family.unwrap();
 
-        if unsafe{vk::vkCreateCommandPool(self.device, &pool_info, null(), &mut self.command_pool)} != vk::VK_SUCCESS {
-            panic!("failed to create command pool");
-        }
+        let mut command_pool = vk::VkCommandPool::default();
+        check(
+            unsafe{vk::vkCreateCommandPool(self.device.raw(), &pool_info, null(), &mut command_pool)},
+            Stage::CommandPool,
+            "failed to create command pool"
+        )?;
+        self.command_pool = CommandPool::new(self.device.raw(), command_pool);
+
+        // only single use command buffers for uploads come from here
+        pool_info.flags = vk::VK_COMMAND_POOL_CREATE_TRANSIENT_BIT as _;
+        pool_info.queueFamilyIndex = queue_families.transfer_family().unwrap();
+
+        let mut transfer_command_pool = vk::VkCommandPool::default();
+        check(
+            unsafe{vk::vkCreateCommandPool(self.device.raw(), &pool_info, null(), &mut transfer_command_pool)},
+            Stage::CommandPool,
+            "failed to create transfer command pool"
+        )?;
+        self.transfer_command_pool = CommandPool::new(self.device.raw(), transfer_command_pool);
+
+        Ok(())
     }
 
 
-    fn create_framebuffers(&mut self) {
-        self.swap_chain_framebuffers.resize(self.swap_chain_image_views.len(), vk::VkFramebuffer::default());
-        for i in 0..self.swap_chain_image_views.len() {
-            let attachments = [self.swap_chain_image_views[i]];
+    fn create_framebuffers(&mut self) -> Result<()> {
+        self.swap_chain_framebuffers.clear();
+        for image_view in &self.swap_chain_image_views {
+            // every framebuffer shares the depth and multisampled color images, only one frame renders into them at a time
+            let attachments = if self.msaa_samples == vk::VK_SAMPLE_COUNT_1_BIT {
+                vec![image_view.raw(), self.depth_image_view.raw()]
+            } else {
+                vec![self.color_image_view.raw(), self.depth_image_view.raw(), image_view.raw()]
+            };
 
             let mut framebuffer_create_info = vk::VkFramebufferCreateInfo::default();
             framebuffer_create_info.sType = vk::VK_STRUCTURE_TYPE_FRAMEBUFFER_CREATE_INFO;
-            framebuffer_create_info.renderPass = self.render_pass;
-            framebuffer_create_info.attachmentCount = 1;
+            framebuffer_create_info.renderPass = self.render_pass.raw();
+            framebuffer_create_info.attachmentCount = attachments.len() as u32;
             framebuffer_create_info.pAttachments = attachments.as_ptr();
             framebuffer_create_info.width = self.swap_chain_extent.width;
             framebuffer_create_info.height = self.swap_chain_extent.height;
             framebuffer_create_info.layers = 1;
 
-            if unsafe{vk::vkCreateFramebuffer(
-                self.device, 
-                &framebuffer_create_info, 
-                null(), 
-                &mut self.swap_chain_framebuffers[i]
-            )} != vk::VK_SUCCESS {
-                panic!("failed to create framebuffer");
-            }
+            let mut framebuffer = vk::VkFramebuffer::default();
+            check(
+                unsafe{vk::vkCreateFramebuffer(
+                    self.device.raw(), 
+                    &framebuffer_create_info, 
+                    null(), 
+                    &mut framebuffer
+                )},
+                Stage::Framebuffers,
+                "failed to create framebuffer"
+            )?;
+            self.swap_chain_framebuffers.push(Framebuffer::new(self.device.raw(), framebuffer));
+        }
+
+        Ok(())
+    }
+
+
+    /// The first of `candidates` whose `tiling` supports all of `features`.
+    fn find_supported_format(
+        &self,
+        candidates: &[vk::VkFormat],
+        tiling: vk::VkImageTiling,
+        features: vk::VkFormatFeatureFlags
+    ) -> Option<vk::VkFormat> {
+        candidates.iter().copied().find(|&format| {
+            let mut properties = vk::VkFormatProperties::default();
+            unsafe{vk::vkGetPhysicalDeviceFormatProperties(self.physical_device, format, &mut properties)};
+
+            let supported = if tiling == vk::VK_IMAGE_TILING_LINEAR {
+                properties.linearTilingFeatures
+            } else {
+                properties.optimalTilingFeatures
+            };
+            supported & features == features
+        })
+    }
+
+
+    fn find_depth_format(&self) -> Result<vk::VkFormat> {
+        match self.find_supported_format(
+            &DEPTH_FORMAT_CANDIDATES,
+            vk::VK_IMAGE_TILING_OPTIMAL,
+            vk::VK_FORMAT_FEATURE_DEPTH_STENCIL_ATTACHMENT_BIT as _
+        ) {
+            Some(format) => Ok(format),
+            None => unsupported(Stage::RenderPass, "none of the depth formats is supported as a depth attachment")
         }
     }
 
 
-    fn create_render_pass(&mut self) {
+    /// The multisampled color image that is rendered into and resolved into the swapchain image.
+    /// Not needed without multisampling.
+    fn create_color_resources(&mut self) -> Result<()> {
+        if self.msaa_samples == vk::VK_SAMPLE_COUNT_1_BIT {return Ok(())}
+
+        let (image, image_memory) = self.create_image(
+            self.swap_chain_extent.width,
+            self.swap_chain_extent.height,
+            1,
+            self.msaa_samples,
+            self.swap_chain_image_format,
+            vk::VK_IMAGE_TILING_OPTIMAL,
+            (vk::VK_IMAGE_USAGE_TRANSIENT_ATTACHMENT_BIT | vk::VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT) as _,
+            vk::VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as _
+        )?;
+
+        self.color_image_view = self.create_image_view(
+            image.raw(),
+            self.swap_chain_image_format,
+            vk::VK_IMAGE_ASPECT_COLOR_BIT as _,
+            1,
+            Stage::ImageViews
+        )?;
+        self.color_image = image;
+        self.color_image_memory = image_memory;
+
+        Ok(())
+    }
+
+
+    /// The largest sample count up to the requested one that the device supports for both the color and the depth
+    /// attachments of a framebuffer.
+    fn usable_sample_count(&self) -> vk::VkSampleCountFlagBits {
+        let mut device_properties = vk::VkPhysicalDeviceProperties::default();
+        unsafe{vk::vkGetPhysicalDeviceProperties(self.physical_device, &mut device_properties)};
+
+        let supported = device_properties.limits.framebufferColorSampleCounts
+            & device_properties.limits.framebufferDepthSampleCounts;
+        let requested = self.requested_msaa_samples.max(1);
+
+        // sample count flags are the sample counts themselves
+        [
+            vk::VK_SAMPLE_COUNT_64_BIT,
+            vk::VK_SAMPLE_COUNT_32_BIT,
+            vk::VK_SAMPLE_COUNT_16_BIT,
+            vk::VK_SAMPLE_COUNT_8_BIT,
+            vk::VK_SAMPLE_COUNT_4_BIT,
+            vk::VK_SAMPLE_COUNT_2_BIT
+        ]
+            .into_iter()
+            .find(|&count| count as u32 <= requested && supported & count as vk::VkSampleCountFlags != 0)
+            .unwrap_or(vk::VK_SAMPLE_COUNT_1_BIT)
+    }
+
+
+    /// A depth image covering the swapchain extent, recreated along with the swapchain.
+    fn create_depth_resources(&mut self) -> Result<()> {
+        let (image, image_memory) = self.create_image(
+            self.swap_chain_extent.width,
+            self.swap_chain_extent.height,
+            1,
+            self.msaa_samples,
+            self.depth_format,
+            vk::VK_IMAGE_TILING_OPTIMAL,
+            vk::VK_IMAGE_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT as _,
+            vk::VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as _
+        )?;
+
+        let mut aspect_mask = vk::VK_IMAGE_ASPECT_DEPTH_BIT as vk::VkImageAspectFlags;
+        if has_stencil_component(self.depth_format) {
+            aspect_mask |= vk::VK_IMAGE_ASPECT_STENCIL_BIT as vk::VkImageAspectFlags;
+        }
+        self.depth_image_view = self.create_image_view(image.raw(), self.depth_format, aspect_mask, 1, Stage::ImageViews)?;
+        self.depth_image = image;
+        self.depth_image_memory = image_memory;
+
+        Ok(())
+    }
+
+
+    fn create_render_pass(&mut self) -> Result<()> {
+        self.depth_format = self.find_depth_format()?;
+        let multisampled = self.msaa_samples != vk::VK_SAMPLE_COUNT_1_BIT;
+
+        let final_layout = if self.headless {
+            vk::VK_IMAGE_LAYOUT_TRANSFER_SRC_OPTIMAL
+        } else {
+            vk::VK_IMAGE_LAYOUT_PRESENT_SRC_KHR
+        };
+
+        // when multisampling, this is the multisampled image and only its resolved copy is kept
         let mut color_attachment = vk::VkAttachmentDescription::default();
         color_attachment.format = self.swap_chain_image_format;
-        color_attachment.samples = vk::VK_SAMPLE_COUNT_1_BIT;
+        color_attachment.samples = self.msaa_samples;
         color_attachment.loadOp = vk::VK_ATTACHMENT_LOAD_OP_CLEAR;
-        color_attachment.storeOp = vk::VK_ATTACHMENT_STORE_OP_STORE;
+        color_attachment.storeOp = if multisampled {
+            vk::VK_ATTACHMENT_STORE_OP_DONT_CARE
+        } else {
+            vk::VK_ATTACHMENT_STORE_OP_STORE
+        };
         color_attachment.stencilLoadOp = vk::VK_ATTACHMENT_LOAD_OP_DONT_CARE;
         color_attachment.stencilStoreOp = vk::VK_ATTACHMENT_STORE_OP_DONT_CARE;
         color_attachment.initialLayout = vk::VK_IMAGE_LAYOUT_UNDEFINED;
-        color_attachment.finalLayout = vk::VK_IMAGE_LAYOUT_PRESENT_SRC_KHR;
+        color_attachment.finalLayout = if multisampled {
+            vk::VK_IMAGE_LAYOUT_COLOR_ATTACHMENT_OPTIMAL
+        } else {
+            final_layout
+        };
 
         let mut color_attachment_ref = vk::VkAttachmentReference::default();
         color_attachment_ref.attachment = 0;
         color_attachment_ref.layout = vk::VK_IMAGE_LAYOUT_COLOR_ATTACHMENT_OPTIMAL;
 
+        // the depth contents are only needed while the subpass runs
+        let mut depth_attachment = vk::VkAttachmentDescription::default();
+        depth_attachment.format = self.depth_format;
+        depth_attachment.samples = self.msaa_samples;
+        depth_attachment.loadOp = vk::VK_ATTACHMENT_LOAD_OP_CLEAR;
+        depth_attachment.storeOp = vk::VK_ATTACHMENT_STORE_OP_DONT_CARE;
+        depth_attachment.stencilLoadOp = vk::VK_ATTACHMENT_LOAD_OP_DONT_CARE;
+        depth_attachment.stencilStoreOp = vk::VK_ATTACHMENT_STORE_OP_DONT_CARE;
+        depth_attachment.initialLayout = vk::VK_IMAGE_LAYOUT_UNDEFINED;
+        depth_attachment.finalLayout = vk::VK_IMAGE_LAYOUT_DEPTH_STENCIL_ATTACHMENT_OPTIMAL;
+
+        let mut depth_attachment_ref = vk::VkAttachmentReference::default();
+        depth_attachment_ref.attachment = 1;
+        depth_attachment_ref.layout = vk::VK_IMAGE_LAYOUT_DEPTH_STENCIL_ATTACHMENT_OPTIMAL;
+
+        let mut resolve_attachment = vk::VkAttachmentDescription::default();
+        resolve_attachment.format = self.swap_chain_image_format;
+        resolve_attachment.samples = vk::VK_SAMPLE_COUNT_1_BIT;
+        resolve_attachment.loadOp = vk::VK_ATTACHMENT_LOAD_OP_DONT_CARE;
+        resolve_attachment.storeOp = vk::VK_ATTACHMENT_STORE_OP_STORE;
+        resolve_attachment.stencilLoadOp = vk::VK_ATTACHMENT_LOAD_OP_DONT_CARE;
+        resolve_attachment.stencilStoreOp = vk::VK_ATTACHMENT_STORE_OP_DONT_CARE;
+        resolve_attachment.initialLayout = vk::VK_IMAGE_LAYOUT_UNDEFINED;
+        resolve_attachment.finalLayout = final_layout;
+
+        let mut resolve_attachment_ref = vk::VkAttachmentReference::default();
+        resolve_attachment_ref.attachment = 2;
+        resolve_attachment_ref.layout = vk::VK_IMAGE_LAYOUT_COLOR_ATTACHMENT_OPTIMAL;
+
         let mut subpass = vk::VkSubpassDescription::default();
         subpass.pipelineBindPoint = vk::VK_PIPELINE_BIND_POINT_GRAPHICS;
         subpass.colorAttachmentCount = 1;
         subpass.pColorAttachments = &color_attachment_ref;
+        subpass.pDepthStencilAttachment = &depth_attachment_ref;
+        subpass.pResolveAttachments = if multisampled {&resolve_attachment_ref} else {null()};
+
+        let attachments = if multisampled {
+            vec![color_attachment, depth_attachment, resolve_attachment]
+        } else {
+            vec![color_attachment, depth_attachment]
+        };
 
         let mut render_pass_info = vk::VkRenderPassCreateInfo::default();
         render_pass_info.sType = vk::VK_STRUCTURE_TYPE_RENDER_PASS_CREATE_INFO;
-        render_pass_info.attachmentCount = 1;
-        render_pass_info.pAttachments = &color_attachment;
+        render_pass_info.attachmentCount = attachments.len() as u32;
+        render_pass_info.pAttachments = attachments.as_ptr();
         render_pass_info.subpassCount = 1;
         render_pass_info.pSubpasses = &subpass;
 
         let mut dependency = vk::VkSubpassDependency::default();
         dependency.srcSubpass = vk::VK_SUBPASS_EXTERNAL as _;
         dependency.dstSubpass = 0;
-        dependency.srcStageMask = vk::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT as _;
-        dependency.srcAccessMask = 0;
-        dependency.dstStageMask = vk::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT as _;
-        dependency.dstAccessMask = vk::VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT as _;
+        // every framebuffer shares the depth image and the multisampled color image, so neither may be cleared while
+        // the previous frame still writes to it
+        dependency.srcStageMask = (vk::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT
+            | vk::VK_PIPELINE_STAGE_LATE_FRAGMENT_TESTS_BIT) as _;
+        dependency.srcAccessMask = (vk::VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT
+            | vk::VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT) as _;
+        dependency.dstStageMask = (vk::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT
+            | vk::VK_PIPELINE_STAGE_EARLY_FRAGMENT_TESTS_BIT) as _;
+        dependency.dstAccessMask = (vk::VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT
+            | vk::VK_ACCESS_DEPTH_STENCIL_ATTACHMENT_WRITE_BIT) as _;
+
+        // headless frames are copied out right after the render pass instead of being presented
+        let mut readback_dependency = vk::VkSubpassDependency::default();
+        readback_dependency.srcSubpass = 0;
+        readback_dependency.dstSubpass = vk::VK_SUBPASS_EXTERNAL as _;
+        readback_dependency.srcStageMask = vk::VK_PIPELINE_STAGE_COLOR_ATTACHMENT_OUTPUT_BIT as _;
+        readback_dependency.srcAccessMask = vk::VK_ACCESS_COLOR_ATTACHMENT_WRITE_BIT as _;
+        readback_dependency.dstStageMask = vk::VK_PIPELINE_STAGE_TRANSFER_BIT as _;
+        readback_dependency.dstAccessMask = vk::VK_ACCESS_TRANSFER_READ_BIT as _;
+
+        let dependencies = [dependency, readback_dependency];
+        render_pass_info.dependencyCount = if self.headless {2} else {1};
+        render_pass_info.pDependencies = dependencies.as_ptr();
+
+        let mut render_pass = vk::VkRenderPass::default();
+        check(
+            unsafe{vk::vkCreateRenderPass(
+                self.device.raw(), 
+                &render_pass_info, 
+                null(), 
+                &mut render_pass
+            )},
+            Stage::RenderPass,
+            "failed to create render pass"
+        )?;
+        self.render_pass = RenderPass::new(self.device.raw(), render_pass);
+
+        Ok(())
+    }
+
+
+    /// Also creates the descriptor set layout, which is reflected from the shaders.
+    fn create_graphics_pipeline(&mut self) -> Result<()> {
+        let (vertex_shader_code, fragment_shader_code, pipeline_interface) = self.load_shaders()?;
+        self.pipeline_interface = pipeline_interface;
+        self.create_descriptor_set_layout()?;
+
+        let (pipeline_layout, pipeline) = self.build_graphics_pipeline(
+            &vertex_shader_code,
+            &fragment_shader_code,
+            &self.pipeline_interface
+        )?;
+        self.pipeline_layout = pipeline_layout;
+        self.pipeline = pipeline;
+
+        Ok(())
+    }
 
-        render_pass_info.dependencyCount = 1;
-        render_pass_info.pDependencies = &dependency;
 
-        if unsafe{vk::vkCreateRenderPass(
-            self.device, 
-            &render_pass_info, 
-            null(), 
-            &mut self.render_pass
-        )} != vk::VK_SUCCESS {
-            panic!("rander pass creation failed")
+    /// Reloads the shaders and swaps in a pipeline built from them. The device is only waited on once the new
+    /// shaders have loaded, and any failure is logged while the current pipeline stays in use. The descriptor set
+    /// layout is kept, so shaders that change their descriptor bindings are rejected.
+    fn reload_graphics_pipeline(&mut self) -> Result<()> {
+        let shaders = self.load_shaders().and_then(|(vertex_shader_code, fragment_shader_code, pipeline_interface)| {
+            let binding_layout = |interface: &PipelineInterface| -> Vec<_> {
+                interface.descriptor_bindings.iter()
+                    .map(|binding| (binding.set, binding.binding, binding.descriptor_type, binding.count, binding.stages))
+                    .collect()
+            };
+            if binding_layout(&pipeline_interface) != binding_layout(&self.pipeline_interface) {
+                return unsupported(Stage::Shaders, "the descriptor bindings changed, which needs a restart");
+            }
+            Ok((vertex_shader_code, fragment_shader_code, pipeline_interface))
+        });
+        let (vertex_shader_code, fragment_shader_code, pipeline_interface) = match shaders {
+            Ok(shaders) => shaders,
+            // a broken shader or a mismatched interface is expected while editing, anything else is not
+            Err(error) if matches!(error.stage(), Stage::Shaders | Stage::Descriptors) => {
+                eprintln!("shader reload: keeping the current pipeline, {}", error);
+                return Ok(());
+            }
+            Err(error) => return Err(error)
+        };
+
+        check(
+            unsafe{vk::vkDeviceWaitIdle(self.device.raw())},
+            Stage::Pipeline,
+            "failed to wait for the device to become idle"
+        )?;
+
+        match self.build_graphics_pipeline(&vertex_shader_code, &fragment_shader_code, &pipeline_interface) {
+            Ok((pipeline_layout, pipeline)) => {
+                // the old pipeline is no longer in use after the wait above
+                self.pipeline = pipeline;
+                self.pipeline_layout = pipeline_layout;
+                self.pipeline_interface = pipeline_interface;
+                println!("shader reload: pipeline rebuilt");
+            }
+            // the current pipeline cannot be kept on a lost device either
+            Err(error) if error.result() == Some(vk::VK_ERROR_DEVICE_LOST) => return Err(error),
+            Err(error) => eprintln!("shader reload: keeping the current pipeline, {}", error)
         }
+
+        Ok(())
     }
 
 
-    fn create_graphics_pipeline(&mut self) {
-        let vertex_shader_code = read_file("./shaders/vert.spv");
-        let fragment_shader_code = read_file("./shaders/frag.spv");
+    /// Loads both shaders and reflects the layout of a pipeline built from them, checking that it fits the `Vertex`
+    /// buffer and the descriptors the application provides.
+    fn load_shaders(&self) -> Result<(Vec<u32>, Vec<u32>, PipelineInterface)> {
+        let vertex_shader_code = self.shaders.vertex.load()?;
+        let fragment_shader_code = self.shaders.fragment.load()?;
 
-        let vert_shader_module = self.create_shader_module(&vertex_shader_code);
-        let frag_shader_module = self.create_shader_module(&fragment_shader_code);
+        let vertex_path = self.shaders.vertex.name();
+        let fragment_path = self.shaders.fragment.name();
+        let vertex = reflect(&vertex_shader_code, &vertex_path)?;
+        let fragment = reflect(&fragment_shader_code, &fragment_path)?;
+        if vertex.stage != vk::VK_SHADER_STAGE_VERTEX_BIT as vk::VkShaderStageFlags {
+            return unsupported(Stage::Shaders, &format!("{} is not a vertex shader", vertex_path));
+        }
+        if fragment.stage != vk::VK_SHADER_STAGE_FRAGMENT_BIT as vk::VkShaderStageFlags {
+            return unsupported(Stage::Shaders, &format!("{} is not a fragment shader", fragment_path));
+        }
+        let pipeline_interface = PipelineInterface::new(&vertex, &fragment, &format!("{} and {}", vertex_path, fragment_path))?;
+
+        if !pipeline_interface.matches_vertex_input(&Vertex::binding_description(), &Vertex::attribute_descriptions()) {
+            return unsupported(Stage::Shaders, &format!("the inputs of {} do not match the layout of Vertex", vertex_path));
+        }
+
+        for binding in &pipeline_interface.descriptor_bindings {
+            let provided = binding.set == 0 && binding.count == 1 && PROVIDED_DESCRIPTORS.iter()
+                .any(|&(provided_binding, descriptor_type)| {
+                    binding.binding == provided_binding && binding.descriptor_type == descriptor_type
+                });
+            if !provided {
+                return unsupported(Stage::Descriptors, &format!(
+                    "{} at set {} binding {} is not a descriptor the application provides",
+                    binding.name, binding.set, binding.binding));
+            }
+        }
+
+        Ok((vertex_shader_code, fragment_shader_code, pipeline_interface))
+    }
+
+
+    fn build_graphics_pipeline(
+        &self,
+        vertex_shader_code: &[u32],
+        fragment_shader_code: &[u32],
+        pipeline_interface: &PipelineInterface
+    ) -> Result<(PipelineLayout, Pipeline)> {
+        // only needed until the pipeline is created
+        let vert_shader_module = self.create_shader_module(vertex_shader_code)?;
+        let frag_shader_module = self.create_shader_module(fragment_shader_code)?;
 
         let mut vert_shader_stage_info = vk::VkPipelineShaderStageCreateInfo::default();
         vert_shader_stage_info.sType = vk::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO;
         vert_shader_stage_info.stage = vk::VK_SHADER_STAGE_VERTEX_BIT;
-        vert_shader_stage_info.module = vert_shader_module;
+        vert_shader_stage_info.module = vert_shader_module.raw();
         vert_shader_stage_info.pName = "main\0".as_ptr() as _;
 
         let mut frag_shader_stage_info = vk::VkPipelineShaderStageCreateInfo::default();
         frag_shader_stage_info.sType = vk::VK_STRUCTURE_TYPE_PIPELINE_SHADER_STAGE_CREATE_INFO;
         frag_shader_stage_info.stage = vk::VK_SHADER_STAGE_FRAGMENT_BIT;
-        frag_shader_stage_info.module = frag_shader_module;
+        frag_shader_stage_info.module = frag_shader_module.raw();
         frag_shader_stage_info.pName = "main\0".as_ptr() as _;
 
         let shader_stages = [vert_shader_stage_info, frag_shader_stage_info];
@@ -375,12 +2218,15 @@ impl HelloTriangleApplication {
         dynamic_state.dynamicStateCount = dynamic_states.len() as _;
         dynamic_state.pDynamicStates = dynamic_states.as_ptr();
 
+        let binding_description = pipeline_interface.vertex_binding_description();
+        let attribute_descriptions = &pipeline_interface.vertex_attributes;
+
         let mut vertex_input_info = vk::VkPipelineVertexInputStateCreateInfo::default();
         vertex_input_info.sType = vk::VK_STRUCTURE_TYPE_PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO;
-        vertex_input_info.vertexBindingDescriptionCount = 0;
-        vertex_input_info.pVertexBindingDescriptions = null();
-        vertex_input_info.vertexAttributeDescriptionCount = 0;
-        vertex_input_info.pVertexAttributeDescriptions = null();
+        vertex_input_info.vertexBindingDescriptionCount = 1;
+        vertex_input_info.pVertexBindingDescriptions = &binding_description;
+        vertex_input_info.vertexAttributeDescriptionCount = attribute_descriptions.len() as _;
+        vertex_input_info.pVertexAttributeDescriptions = attribute_descriptions.as_ptr();
 
         let mut input_assembly = vk::VkPipelineInputAssemblyStateCreateInfo::default();
         input_assembly.sType = vk::VK_STRUCTURE_TYPE_PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO;
@@ -420,7 +2266,7 @@ impl HelloTriangleApplication {
         let mut multisampling = vk::VkPipelineMultisampleStateCreateInfo::default();
         multisampling.sType = vk::VK_STRUCTURE_TYPE_PIPELINE_MULTISAMPLE_STATE_CREATE_INFO;
         multisampling.sampleShadingEnable = vk::VK_FALSE;
-        multisampling.rasterizationSamples = vk::VK_SAMPLE_COUNT_1_BIT;
+        multisampling.rasterizationSamples = self.msaa_samples;
         multisampling.minSampleShading = 1.0;
         multisampling.pSampleMask = null();
         multisampling.alphaToCoverageEnable = vk::VK_FALSE;
@@ -449,22 +2295,34 @@ impl HelloTriangleApplication {
         color_blend.blendConstants[2] = 0.0;
         color_blend.blendConstants[3] = 0.0;
 
+        let depth_stencil = self.depth_state.to_vk();
+
+        self.check_push_constant_ranges(&pipeline_interface.push_constant_ranges)?;
+        let push_constant_ranges: Vec<vk::VkPushConstantRange> = pipeline_interface.push_constant_ranges
+            .iter()
+            .map(PushConstantRange::to_vk)
+            .collect();
+
         let mut pipeline_layout_create_info = vk::VkPipelineLayoutCreateInfo::default();
         pipeline_layout_create_info.sType = vk::VK_STRUCTURE_TYPE_PIPELINE_LAYOUT_CREATE_INFO;
-        pipeline_layout_create_info.setLayoutCount = 0;
-        pipeline_layout_create_info.pSetLayouts = null();
-        pipeline_layout_create_info.pushConstantRangeCount = 0;
-        pipeline_layout_create_info.pPushConstantRanges = null();
-
-        if unsafe{vk::vkCreatePipelineLayout(
-            self.device, 
-            &pipeline_layout_create_info, 
-            null(), 
-            &mut 
-            self.pipeline_layout
-        )} != vk::VK_SUCCESS {
-            panic!("failed to create pipeline layout");
-        }
+        let set_layouts = [self.descriptor_set_layout.raw()];
+        pipeline_layout_create_info.setLayoutCount = set_layouts.len() as u32;
+        pipeline_layout_create_info.pSetLayouts = set_layouts.as_ptr();
+        pipeline_layout_create_info.pushConstantRangeCount = push_constant_ranges.len() as u32;
+        pipeline_layout_create_info.pPushConstantRanges = push_constant_ranges.as_ptr();
+
+        let mut pipeline_layout = vk::VkPipelineLayout::default();
+        check(
+            unsafe{vk::vkCreatePipelineLayout(
+                self.device.raw(), 
+                &pipeline_layout_create_info, 
+                null(), 
+                &mut pipeline_layout
+            )},
+            Stage::Pipeline,
+            "failed to create pipeline layout"
+        )?;
+        let pipeline_layout = PipelineLayout::new(self.device.raw(), pipeline_layout);
 
         let mut pipeline_info = vk::VkGraphicsPipelineCreateInfo::default();
         pipeline_info.sType = vk::VK_STRUCTURE_TYPE_GRAPHICS_PIPELINE_CREATE_INFO;
@@ -475,64 +2333,92 @@ impl HelloTriangleApplication {
         pipeline_info.pViewportState = &viewport_state;
         pipeline_info.pRasterizationState = &rasterizer;
         pipeline_info.pMultisampleState = &multisampling;
-        pipeline_info.pDepthStencilState = null();
+        pipeline_info.pDepthStencilState = &depth_stencil;
         pipeline_info.pColorBlendState = &color_blend;
         pipeline_info.pDynamicState = &dynamic_state;
-        pipeline_info.layout = self.pipeline_layout;
-        pipeline_info.renderPass = self.render_pass;
+        pipeline_info.layout = pipeline_layout.raw();
+        pipeline_info.renderPass = self.render_pass.raw();
         pipeline_info.subpass = 0;
         pipeline_info.basePipelineHandle = null_mut();
         pipeline_info.basePipelineIndex = -1;
 
-        if unsafe{vk::vkCreateGraphicsPipelines(
-            self.device, 
-            null_mut(), 
-            1, 
-            &pipeline_info, 
-            null(), 
-            &mut self.pipeline
-        ) != vk::VK_SUCCESS} {
-            panic!("pipeline creation failed");
-        }
+        let mut pipeline = vk::VkPipeline::default();
+        check(
+            unsafe{vk::vkCreateGraphicsPipelines(
+                self.device.raw(), 
+                self.pipeline_cache.raw(), 
+                1, 
+                &pipeline_info, 
+                null(), 
+                &mut pipeline
+            )},
+            Stage::Pipeline,
+            "failed to create graphics pipeline"
+        )?;
+        let pipeline = Pipeline::new(self.device.raw(), pipeline);
+
+        Ok((pipeline_layout, pipeline))
+    }
+    
 
-        unsafe {
-            vk::vkDestroyShaderModule(self.device, vert_shader_module, null());
-            vk::vkDestroyShaderModule(self.device, frag_shader_module, null());
+    fn create_image_views(&mut self) -> Result<()> {
+        self.swap_chain_image_views.clear();
+        for &image in &self.swap_chain_images {
+            let image_view = self.create_image_view(
+                image,
+                self.swap_chain_image_format,
+                vk::VK_IMAGE_ASPECT_COLOR_BIT as _,
+                1,
+                Stage::ImageViews
+            )?;
+            self.swap_chain_image_views.push(image_view);
         }
+
+        Ok(())
     }
-    
 
-    fn create_image_views(&mut self) {
-        self.swap_chain_image_views.resize(self.swap_chain_images.len(), vk::VkImageView::default());
-        for (i, &image) in self.swap_chain_images.iter().enumerate() {
-            let mut create_info = vk::VkImageViewCreateInfo::default();
-            create_info.sType = vk::VK_STRUCTURE_TYPE_IMAGE_VIEW_CREATE_INFO;
-            create_info.image = image;
-            create_info.viewType = vk::VK_IMAGE_VIEW_TYPE_2D;
-            create_info.format = self.swap_chain_image_format;
-            create_info.components.r = vk::VK_COMPONENT_SWIZZLE_IDENTITY;
-            create_info.components.g = vk::VK_COMPONENT_SWIZZLE_IDENTITY;
-            create_info.components.b = vk::VK_COMPONENT_SWIZZLE_IDENTITY;
-            create_info.components.a = vk::VK_COMPONENT_SWIZZLE_IDENTITY;
-            create_info.subresourceRange.aspectMask = vk::VK_IMAGE_ASPECT_COLOR_BIT as _;
-            create_info.subresourceRange.baseMipLevel = 0;
-            create_info.subresourceRange.levelCount = 1;
-            create_info.subresourceRange.baseArrayLayer = 0;
-            create_info.subresourceRange.layerCount = 1;
-
-            if unsafe{vk::vkCreateImageView(
-                self.device, 
+
+    /// A 2D view of the first `mip_levels` levels and the first layer of `image`. Failures are reported under `stage`.
+    fn create_image_view(
+        &self,
+        image: vk::VkImage,
+        format: vk::VkFormat,
+        aspect_mask: vk::VkImageAspectFlags,
+        mip_levels: u32,
+        stage: Stage
+    ) -> Result<ImageView> {
+        let mut create_info = vk::VkImageViewCreateInfo::default();
+        create_info.sType = vk::VK_STRUCTURE_TYPE_IMAGE_VIEW_CREATE_INFO;
+        create_info.image = image;
+        create_info.viewType = vk::VK_IMAGE_VIEW_TYPE_2D;
+        create_info.format = format;
+        create_info.components.r = vk::VK_COMPONENT_SWIZZLE_IDENTITY;
+        create_info.components.g = vk::VK_COMPONENT_SWIZZLE_IDENTITY;
+        create_info.components.b = vk::VK_COMPONENT_SWIZZLE_IDENTITY;
+        create_info.components.a = vk::VK_COMPONENT_SWIZZLE_IDENTITY;
+        create_info.subresourceRange.aspectMask = aspect_mask;
+        create_info.subresourceRange.baseMipLevel = 0;
+        create_info.subresourceRange.levelCount = mip_levels;
+        create_info.subresourceRange.baseArrayLayer = 0;
+        create_info.subresourceRange.layerCount = 1;
+
+        let mut image_view = vk::VkImageView::default();
+        check(
+            unsafe{vk::vkCreateImageView(
+                self.device.raw(), 
                 &create_info, 
                 null(), 
-                &mut self.swap_chain_image_views[i]
-            )} != vk::VK_SUCCESS {
-                panic!("image view generation failed")
-            }
-        }
+                &mut image_view
+            )},
+            stage,
+            "failed to create image view"
+        )?;
+
+        Ok(ImageView::new(self.device.raw(), image_view))
     }
 
 
-    fn create_swapchain(&mut self) {
+    fn create_swapchain(&mut self) -> Result<()> {
         let swap_chain_support = self.query_swap_chain_support(self.physical_device);
 
         let surface_format = self.choose_swap_chain_format(&swap_chain_support.formats);
@@ -546,7 +2432,7 @@ impl HelloTriangleApplication {
 
         let mut create_info = vk::VkSwapchainCreateInfoKHR::default();
         create_info.sType = vk::VK_STRUCTURE_TYPE_SWAPCHAIN_CREATE_INFO_KHR;
-        create_info.surface = self.surface;
+        create_info.surface = self.surface.raw();
         create_info.minImageCount = image_count;
         create_info.imageColorSpace = surface_format.colorSpace;
         create_info.imageExtent = extent;
@@ -554,7 +2440,7 @@ impl HelloTriangleApplication {
         create_info.imageUsage = vk::VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT as _;
         create_info.imageFormat = surface_format.format;
 
-        let indices = self.get_queue_families(self.physical_device);
+        let indices = self.queue_family_indices;
         let queue_family_indices: [u32; 2] = [indices.graphics_family.unwrap(), indices.present_family.unwrap()];
         if indices.graphics_family != indices.present_family {
             create_info.imageSharingMode = vk::VK_SHARING_MODE_CONCURRENT;
@@ -570,68 +2456,187 @@ impl HelloTriangleApplication {
         create_info.compositeAlpha = vk::VK_COMPOSITE_ALPHA_OPAQUE_BIT_KHR;
         create_info.presentMode = present_mode;
         create_info.clipped = vk::VK_TRUE;
-        create_info.oldSwapchain = null_mut();
 
-        if unsafe{vk::vkCreateSwapchainKHR(
-            self.device, 
-            &mut create_info, 
-            null(), 
-            &mut self.swap_chain
-        )} != vk::VK_SUCCESS {
-            panic!("swapchain generation failed");
-        }
+        // null on first creation, the retired swapchain when recreating
+        create_info.oldSwapchain = self.swap_chain.raw();
+
+        let mut swap_chain = vk::VkSwapchainKHR::default();
+        check(
+            unsafe{vk::vkCreateSwapchainKHR(
+                self.device.raw(), 
+                &mut create_info, 
+                null(), 
+                &mut swap_chain
+            )},
+            Stage::Swapchain,
+            "failed to create swapchain"
+        )?;
+        // drops the retired swapchain
+        self.swap_chain = Swapchain::new(self.device.raw(), swap_chain);
 
         unsafe {
-            vk::vkGetSwapchainImagesKHR(self.device, self.swap_chain, &mut image_count, null_mut());
+            vk::vkGetSwapchainImagesKHR(self.device.raw(), self.swap_chain.raw(), &mut image_count, null_mut());
             self.swap_chain_images.resize(image_count as usize, vk::VkImage::default());
-            vk::vkGetSwapchainImagesKHR(self.device, self.swap_chain, &mut image_count, self.swap_chain_images.as_mut_ptr());
+            vk::vkGetSwapchainImagesKHR(self.device.raw(), self.swap_chain.raw(), &mut image_count, self.swap_chain_images.as_mut_ptr());
             self.swap_chain_image_format = surface_format.format;
             self.swap_chain_extent = extent;
         }
+
+        Ok(())
     }
 
 
-    fn create_surface(&mut self) {
-        unsafe {
-            if glfwCreateWindowSurface(
-                self.instance as _, 
-                self.window, 
+    /// Headless stand-in for `create_swapchain`: a single device-local color image that
+    /// takes the place of the swapchain images and can be copied back to the host.
+    fn create_offscreen_target(&mut self) -> Result<()> {
+        let (image, image_memory) = self.create_image(
+            self.swap_chain_extent.width,
+            self.swap_chain_extent.height,
+            1,
+            vk::VK_SAMPLE_COUNT_1_BIT,
+            OFFSCREEN_FORMAT,
+            vk::VK_IMAGE_TILING_OPTIMAL,
+            (vk::VK_IMAGE_USAGE_COLOR_ATTACHMENT_BIT | vk::VK_IMAGE_USAGE_TRANSFER_SRC_BIT) as _,
+            vk::VK_MEMORY_PROPERTY_DEVICE_LOCAL_BIT as _
+        )?;
+
+        self.swap_chain_images = vec![image.raw()];
+        self.swap_chain_image_format = OFFSCREEN_FORMAT;
+        self.offscreen_image = image;
+        self.offscreen_image_memory = image_memory;
+        self.create_image_views()
+    }
+
+
+    fn create_surface(&mut self) -> Result<()> {
+        let mut surface = vk::VkSurfaceKHR::default();
+        check(
+            unsafe{glfwCreateWindowSurface(
+                self.instance.raw() as _, 
+                self.window.raw(), 
                 null(), 
-                &mut self.surface as *mut _ as _
-            ) != vk::VK_SUCCESS {
-                panic!("failed to create surface");
-            }
+                &mut surface as *mut _ as _
+            )},
+            Stage::Surface,
+            "failed to create window surface"
+        )?;
+        self.surface = Surface::new(self.instance.raw(), surface);
+
+        Ok(())
+    }
+
+
+    fn pipeline_cache_path(&self) -> PathBuf {
+        self.pipeline_cache_path.clone().unwrap_or_else(vk_pipeline_cache::default_path)
+    }
+
+
+    /// Seeds the pipeline cache with the data saved by a previous run on the same device and driver, if there is any
+    /// and the cache is not disabled.
+    fn create_pipeline_cache(&mut self) -> Result<()> {
+        let mut device_properties = vk::VkPhysicalDeviceProperties::default();
+        unsafe{vk::vkGetPhysicalDeviceProperties(self.physical_device, &mut device_properties)};
+        let initial_data = if self.pipeline_cache_disabled {
+            Vec::new()
+        } else {
+            vk_pipeline_cache::load(&self.pipeline_cache_path(), &device_properties).unwrap_or_default()
+        };
+
+        let mut create_info = vk::VkPipelineCacheCreateInfo::default();
+        create_info.sType = vk::VK_STRUCTURE_TYPE_PIPELINE_CACHE_CREATE_INFO;
+        create_info.initialDataSize = initial_data.len();
+        create_info.pInitialData = if initial_data.is_empty() {null()} else {initial_data.as_ptr() as _};
+
+        let mut pipeline_cache = vk::VkPipelineCache::default();
+        check(
+            unsafe{vk::vkCreatePipelineCache(self.device.raw(), &create_info, null(), &mut pipeline_cache)},
+            Stage::Pipeline,
+            "failed to create pipeline cache"
+        )?;
+        self.pipeline_cache = PipelineCache::new(self.device.raw(), pipeline_cache);
+
+        Ok(())
+    }
+
+
+    /// Writes the pipeline cache back to its file. Runs on drop, so failures are only reported.
+    fn save_pipeline_cache(&self) {
+        if self.pipeline_cache.raw().is_null() || self.pipeline_cache_disabled {
+            return;
+        }
+
+        let mut data_size: usize = 0;
+        let result = unsafe{vk::vkGetPipelineCacheData(self.device.raw(), self.pipeline_cache.raw(), &mut data_size, null_mut())};
+        if result != vk::VK_SUCCESS {
+            eprintln!("pipeline cache: could not get the cache data ({})", result_name(result));
+            return;
+        }
+        let mut data = vec![0u8; data_size];
+        let result = unsafe{vk::vkGetPipelineCacheData(
+            self.device.raw(),
+            self.pipeline_cache.raw(),
+            &mut data_size,
+            data.as_mut_ptr() as _
+        )};
+        if result != vk::VK_SUCCESS {
+            eprintln!("pipeline cache: could not get the cache data ({})", result_name(result));
+            return;
+        }
+        data.truncate(data_size);
+
+        let path = self.pipeline_cache_path();
+        if let Err(error) = vk_pipeline_cache::save(&path, &data) {
+            eprintln!("pipeline cache: could not write {}: {}", path.display(), error);
         }
     }
 
 
-    fn create_logical_device(&mut self) {
+    fn create_logical_device(&mut self) -> Result<()> {
         let indices = self.get_queue_families(self.physical_device);
 
+        // has to outlive `vkCreateDevice`, every create info points at it
+        let queue_priority: f32 = 1.0;
         let mut queue_create_infos:Vec<vk::VkDeviceQueueCreateInfo> = Vec::new();
-        let unique_queue_families = HashSet::from(
-            [indices.graphics_family.unwrap(), indices.present_family.unwrap()]
-        );
-
-        for family in unique_queue_families {
+        for family in indices.unique_families() {
             let mut queue_create_info = vk::VkDeviceQueueCreateInfo::default();
             queue_create_info.sType = vk::VK_STRUCTURE_TYPE_DEVICE_QUEUE_CREATE_INFO;
             queue_create_info.queueFamilyIndex = family;
             queue_create_info.queueCount = 1;
-            let queue_priority:f32 = 1.0;
             queue_create_info.pQueuePriorities = &queue_priority;
             queue_create_infos.push(queue_create_info);
         }
 
-        let physical_device_features = vk::VkPhysicalDeviceFeatures::default();
+        let mut device_properties = vk::VkPhysicalDeviceProperties::default();
+        unsafe{vk::vkGetPhysicalDeviceProperties(self.physical_device, &mut device_properties)};
+        let api_version = vk_version::usable_on_device(self.api_version, device_properties.apiVersion);
+        let available_extensions = self.available_device_extensions(self.physical_device);
+        let available_features = DeviceFeatures::query(
+            self.instance.raw(), self.physical_device, api_version, &available_extensions
+        );
+        let enabled_extensions = self.device_extensions()
+            .enabled(&available_extensions, &available_features, self.api_version, api_version);
+        let mut enabled_features = self.device_requirements()
+            .enabled_features(&available_features)
+            .union(&extension_features(&enabled_extensions));
+
+        // the names come from the known extensions, so none of them has a nul inside
+        let extension_names: Vec<Vec<u8>> = enabled_extensions.iter().map(|name| format!("{}\0", name).into_bytes()).collect();
+        let extension_name_pointers: Vec<*const std::ffi::c_char> = extension_names.iter().map(|name| name.as_ptr() as _).collect();
 
         let mut create_info = vk::VkDeviceCreateInfo::default();
         create_info.sType = vk::VK_STRUCTURE_TYPE_DEVICE_CREATE_INFO;
         create_info.pQueueCreateInfos = queue_create_infos.as_ptr();
         create_info.queueCreateInfoCount = queue_create_infos.len() as u32;
-        create_info.pEnabledFeatures = &physical_device_features;
-        create_info.enabledExtensionCount = DEVICE_EXTENSIONS.len() as _;
-        create_info.ppEnabledExtensionNames = DEVICE_EXTENSIONS.as_ptr() as _;
+        // the chained structs can only be passed through a VkPhysicalDeviceFeatures2 in pNext
+        let features2 = enabled_features.chain(api_version, &enabled_extensions);
+        if enabled_features.has_chained() {
+            create_info.pNext = &features2 as *const _ as _;
+            create_info.pEnabledFeatures = null();
+        } else {
+            create_info.pEnabledFeatures = &enabled_features.core;
+        }
+        create_info.enabledExtensionCount = extension_name_pointers.len() as _;
+        create_info.ppEnabledExtensionNames = extension_name_pointers.as_ptr() as _;
 
         if ENABLE_VALIDATION_LAYERS {
             create_info.enabledLayerCount = VALIDATION_LAYERS.len() as u32;
@@ -640,55 +2645,100 @@ impl HelloTriangleApplication {
             create_info.enabledLayerCount = 0;
         }
 
-        if unsafe{vk::vkCreateDevice(self.physical_device, &mut create_info, null(), &mut self.device)} != vk::VK_SUCCESS {
-            panic!("could not initialize logical device");
-        }
+        let mut device = vk::VkDevice::default();
+        check(
+            unsafe{vk::vkCreateDevice(self.physical_device, &mut create_info, null(), &mut device)},
+            Stage::LogicalDevice,
+            "failed to create logical device"
+        )?;
+        self.device = Device::new(device);
+        self.enabled_device_extensions = enabled_extensions;
+        self.enabled_features = enabled_features;
+        self.device_api_version = api_version;
 
         unsafe{
-            vk::vkGetDeviceQueue(self.device, indices.graphics_family.unwrap(), 0, &mut self.graphics_queue);
-            vk::vkGetDeviceQueue(self.device, indices.present_family.unwrap(), 0, &mut self.present_queue);
+            vk::vkGetDeviceQueue(self.device.raw(), indices.graphics_family.unwrap(), 0, &mut self.graphics_queue);
+            vk::vkGetDeviceQueue(self.device.raw(), indices.present_family.unwrap(), 0, &mut self.present_queue);
+            vk::vkGetDeviceQueue(self.device.raw(), indices.transfer_family().unwrap(), 0, &mut self.transfer_queue);
+            vk::vkGetDeviceQueue(self.device.raw(), indices.compute_family().unwrap(), 0, &mut self.compute_queue);
         }
+        self.queue_family_indices = indices;
 
+        Ok(())
     }
 
 
-    fn pick_physical_device(&mut self) {
-        unsafe {
+    fn pick_physical_device(&mut self) -> Result<()> {
+        let candidates = self.device_candidates()?;
+        let selected = select(&candidates, &self.device_selector)
+            .map_err(|context| AppError::Unsupported {stage: Stage::PhysicalDevice, context})?;
+        self.physical_device = selected.handle;
+        self.msaa_samples = self.usable_sample_count();
+
+        Ok(())
+    }
+
+
+    /// Every physical device in enumeration order, rated by `rate_device_suitability`.
+    fn device_candidates(&self) -> Result<Vec<DeviceCandidate>> {
+        let physical_devices = unsafe {
             let mut device_count:u32 = 0;
-            vk::vkEnumeratePhysicalDevices(self.instance, &mut device_count, null_mut());
+            check(
+                vk::vkEnumeratePhysicalDevices(self.instance.raw(), &mut device_count, null_mut()),
+                Stage::PhysicalDevice,
+                "failed to enumerate physical devices"
+            )?;
             if device_count == 0 {
-                panic!("failed to find gpus with vulkan support");
+                return unsupported(Stage::PhysicalDevice, "failed to find gpus with vulkan support");
             }
             let mut physical_devices: Vec<vk::VkPhysicalDevice> = Vec::with_capacity(device_count as usize);
-            vk::vkEnumeratePhysicalDevices(self.instance, &mut device_count, physical_devices.as_mut_ptr());
+            check(
+                vk::vkEnumeratePhysicalDevices(self.instance.raw(), &mut device_count, physical_devices.as_mut_ptr()),
+                Stage::PhysicalDevice,
+                "failed to enumerate physical devices"
+            )?;
             physical_devices.set_len(device_count as usize);
 
-            let mut rankings = BTreeMap::new();
-
-            for device in physical_devices {
-                let ranking = self.rate_device_suitability(device);
-                rankings.insert(ranking, device);
+            physical_devices
+        };
+
+        let candidates = physical_devices.into_iter().enumerate().map(|(index, device)| {
+            let mut device_properties = vk::VkPhysicalDeviceProperties::default();
+            unsafe{vk::vkGetPhysicalDeviceProperties(device, &mut device_properties)};
+            let name = unsafe{std::ffi::CStr::from_ptr(device_properties.deviceName.as_ptr())};
+
+            DeviceCandidate {
+                index,
+                name: name.to_string_lossy().into_owned(),
+                device_type: device_properties.deviceType,
+                vendor_id: device_properties.vendorID,
+                device_id: device_properties.deviceID,
+                driver_version: device_properties.driverVersion,
+                api_version: device_properties.apiVersion,
+                suitability: self.rate_device_suitability(device),
+                handle: device
             }
+        });
 
-            let final_score;
-            (final_score, self.physical_device) = rankings.last_entry().unwrap().remove_entry();
-
-            if final_score == 0 {
-                panic!("failed to find a suitable gpu");
-            }
-        }
+        Ok(candidates.collect())
     }
 
 
-    fn setup_debug_messanger(&mut self) {
-        if !ENABLE_VALIDATION_LAYERS {return}
+    fn setup_debug_messanger(&mut self) -> Result<()> {
+        if !ENABLE_VALIDATION_LAYERS {return Ok(())}
 
         let mut create_info = vk::VkDebugUtilsMessengerCreateInfoEXT::default();
         populate_debug_messenger_create_info(&mut create_info);
 
-        if create_debug_utils_messenger(self.instance, &create_info, null(), &mut self.debug_messenger) != vk::VK_SUCCESS {
-            panic!("could not set up debug messenger")
-        }
+        let mut debug_messenger = vk::VkDebugUtilsMessengerEXT::default();
+        check(
+            create_debug_utils_messenger(self.instance.raw(), &create_info, null(), &mut debug_messenger),
+            Stage::DebugMessenger,
+            "failed to set up debug messenger"
+        )?;
+        self.debug_messenger = DebugMessenger::new(self.instance.raw(), debug_messenger);
+
+        Ok(())
     }
 
 
@@ -718,10 +2768,13 @@ impl HelloTriangleApplication {
 
     fn get_required_extensions(&self) -> Vec<*const i8> {
         unsafe {
-            let mut glfw_extension_count:u32 = 0;
-            let glfw_extensions = glfwGetRequiredInstanceExtensions(&mut glfw_extension_count);
-            let extensions_slice = std::slice::from_raw_parts(glfw_extensions, glfw_extension_count as usize);
-            let mut extensions = extensions_slice.to_vec();
+            let mut extensions = Vec::new();
+            if !self.headless {
+                let mut glfw_extension_count:u32 = 0;
+                let glfw_extensions = glfwGetRequiredInstanceExtensions(&mut glfw_extension_count);
+                let extensions_slice = std::slice::from_raw_parts(glfw_extensions, glfw_extension_count as usize);
+                extensions.extend_from_slice(extensions_slice);
+            }
 
             if ENABLE_VALIDATION_LAYERS {
                 extensions.push(vk::VK_EXT_DEBUG_UTILS_EXTENSION_NAME.as_ptr() as _);
@@ -732,9 +2785,14 @@ impl HelloTriangleApplication {
     }
 
 
-    fn create_instance(&mut self) {
+    fn create_instance(&mut self) -> Result<()> {
         if ENABLE_VALIDATION_LAYERS && !self.check_validation_layer_support() {
-            panic!("validation layers requested, but not available");
+            return unsupported(Stage::Instance, "validation layers requested, but not available");
+        }
+
+        let ceiling = self.api_version_ceiling.unwrap_or(vk_version::LATEST);
+        if ceiling < vk_version::VULKAN_1_0 {
+            return unsupported(Stage::Instance, &format!("Vulkan {} is not a usable version", vk_version::name(ceiling)));
         }
 
         let mut appinfo = vk::VkApplicationInfo::default();
@@ -742,8 +2800,9 @@ impl HelloTriangleApplication {
         appinfo.pApplicationName = "Hello Triangle\0".as_ptr() as _;
         appinfo.applicationVersion = 0;
         appinfo.pEngineName = "No Engine\0".as_ptr() as _;
-        appinfo.engineVersion = get_api_version(1,0);
-        appinfo.apiVersion = get_api_version(1,0); 
+        appinfo.engineVersion = make_api_version(0, 1, 0, 0);
+        appinfo.apiVersion = vk_version::negotiate(vk_version::instance_version(), ceiling);
+        self.api_version = appinfo.apiVersion;
 
         let mut create_info = vk::VkInstanceCreateInfo::default();
         create_info.sType = vk::VK_STRUCTURE_TYPE_INSTANCE_CREATE_INFO;
@@ -777,100 +2836,169 @@ impl HelloTriangleApplication {
         //    }
         //}
 
-        let res: vk::VkResult = unsafe{vk::vkCreateInstance(&create_info, null(), &mut self.instance)};
-        if res != vk::VK_SUCCESS {
-            panic!("failed to create instance!");
-        }
+        let mut instance = vk::VkInstance::default();
+        let res: vk::VkResult = unsafe{vk::vkCreateInstance(&create_info, null(), &mut instance)};
+        check(res, Stage::Instance, "failed to create instance")?;
+        self.instance = Instance::new(instance);
+
+        Ok(())
     }
 
+    /// Picks the queue families of `device`. Graphics and presentation prefer one family that can do both, so
+    /// swapchain images need no sharing. Transfer and compute only get a family without graphics support, since
+    /// those run on other hardware than the rendering, and are `None` otherwise. Of equally suitable families the
+    /// first one wins.
     pub fn get_queue_families(&self, device: vk::VkPhysicalDevice) -> QueueFamilyIndices {
         let mut indices = QueueFamilyIndices::default();
 
-        unsafe {
+        let queue_families = unsafe {
             let mut queue_family_count:u32 = 0;
             vk::vkGetPhysicalDeviceQueueFamilyProperties(device, &mut queue_family_count, null_mut());
             let mut queue_families: Vec<vk::VkQueueFamilyProperties> = Vec::with_capacity(queue_family_count as usize);
             vk::vkGetPhysicalDeviceQueueFamilyProperties(device, &mut queue_family_count, queue_families.as_mut_ptr());
             queue_families.set_len(queue_family_count as usize);
-
-
-            for (i, &family_properties) in queue_families.iter().enumerate() {
-                if (family_properties.queueFlags & vk::VK_QUEUE_GRAPHICS_BIT as u32) > 0 {
-                    indices.graphics_family = Some(i as u32);
-                }
-
-                let mut present_support: vk::VkBool32 = vk::VK_FALSE;
-                vk::vkGetPhysicalDeviceSurfaceSupportKHR(
-                    device, 
-                    i as u32, 
-                    self.surface, 
-                    &mut present_support
-                );
-
-                if present_support == vk::VK_TRUE {
-                    indices.present_family = Some(i as u32);
-                }
-
-                if indices.is_complete() {return indices}
+            queue_families
+        };
+
+        let families_with = |required: vk::VkQueueFlagBits, excluded: vk::VkQueueFlagBits| -> Vec<u32> {
+            queue_families.iter()
+                .enumerate()
+                .filter(|(_, family)| family.queueCount > 0)
+                .filter(|(_, family)| family.queueFlags & required as u32 != 0 && family.queueFlags & excluded as u32 == 0)
+                .map(|(index, _)| index as u32)
+                .collect()
+        };
+        let supports_present = |family: u32| {
+            // nothing is presented without a surface, the graphics queue stands in for the present queue
+            if self.headless {
+                return true;
+            }
+            let mut present_support: vk::VkBool32 = vk::VK_FALSE;
+            unsafe{vk::vkGetPhysicalDeviceSurfaceSupportKHR(device, family, self.surface.raw(), &mut present_support)};
+            present_support == vk::VK_TRUE
+        };
+
+        let graphics_families = families_with(vk::VK_QUEUE_GRAPHICS_BIT, 0);
+        match graphics_families.iter().copied().find(|&family| supports_present(family)) {
+            Some(family) => {
+                indices.graphics_family = Some(family);
+                indices.present_family = Some(family);
+            }
+            None => {
+                indices.graphics_family = graphics_families.first().copied();
+                indices.present_family = (0..queue_families.len() as u32).find(|&family| supports_present(family));
             }
         }
 
+        // compute families can always transfer, even when they do not report the transfer bit. Families that can
+        // do nothing else are usually dedicated copy engines.
+        let transfer_only = families_with(vk::VK_QUEUE_TRANSFER_BIT, vk::VK_QUEUE_GRAPHICS_BIT | vk::VK_QUEUE_COMPUTE_BIT);
+        let compute_families = families_with(vk::VK_QUEUE_COMPUTE_BIT, vk::VK_QUEUE_GRAPHICS_BIT);
+        indices.transfer_family = transfer_only.first().or(compute_families.first()).copied();
+        indices.compute_family = compute_families.first().copied();
+
         indices
     }
 
 
-    fn rate_device_suitability(&self, device: vk::VkPhysicalDevice) -> i32 {
-        let mut score: i32 = 0;
+    fn rate_device_suitability(&self, device: vk::VkPhysicalDevice) -> Suitability {
+        let mut score: u32 = 0;
         let mut device_properties = vk::VkPhysicalDeviceProperties::default();
-        let mut device_features = vk::VkPhysicalDeviceFeatures::default();
-        unsafe {
-            vk::vkGetPhysicalDeviceProperties(device, &mut device_properties);
-            vk::vkGetPhysicalDeviceFeatures(device, &mut device_features);
-        }
+        unsafe{vk::vkGetPhysicalDeviceProperties(device, &mut device_properties)};
+        let api_version = vk_version::usable_on_device(self.api_version, device_properties.apiVersion);
+        let available_extensions = self.available_device_extensions(device);
+        let device_features = DeviceFeatures::query(self.instance.raw(), device, api_version, &available_extensions);
 
         if device_properties.deviceType == vk::VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU {
             score += 1000;
         }
 
-        score += device_properties.limits.maxImageDimension2D as i32;
+        score += device_properties.limits.maxImageDimension2D;
+
+        let requirements = self.device_requirements();
+        let format_features = |format| {
+            let mut format_properties = vk::VkFormatProperties::default();
+            unsafe{vk::vkGetPhysicalDeviceFormatProperties(device, format, &mut format_properties)};
+            format_properties.optimalTilingFeatures
+        };
+        if let Some(reason) = requirements.unmet(&device_features, &device_properties.limits, format_features) {
+            return Suitability::Rejected(reason);
+        }
+        score += 100 * requirements.optional_features.intersection(&device_features).count() as u32;
 
-        if device_features.geometryShader == 0 {
-            eprintln!("failed to find geometry shader support");
-            return 0;
+        let indices = self.get_queue_families(device);
+        if indices.graphics_family.is_none() {
+            return Suitability::Rejected("it has no graphics queue".to_string());
+        }
+        if indices.present_family.is_none() {
+            return Suitability::Rejected("none of its queues can present to the window".to_string());
         }
 
-        if !self.get_queue_families(device).is_complete() {
-            return 0;
+        let device_extensions = self.device_extensions();
+        let unmet_extensions = device_extensions.unmet(&available_extensions, &device_features, self.api_version, api_version);
+        if let Some(reason) = unmet_extensions {
+            return Suitability::Rejected(reason);
         }
 
-        if !self.check_device_extension_support(device) {
-            return 0;
+        if self.headless {
+            return Suitability::Suitable {score};
         }
 
         let swap_chain_support_details = self.query_swap_chain_support(device);
         let swap_chain_supported = !swap_chain_support_details.formats.is_empty() && !swap_chain_support_details.present_modes.is_empty();
         if !swap_chain_supported {
-            return 0;
+            return Suitability::Rejected("it offers no surface formats or present modes for the window".to_string());
         }
 
-        score
+        Suitability::Suitable {score}
     }
 
 
-    fn check_device_extension_support(&self, device: vk::VkPhysicalDevice) -> bool {
+    /// The requirements set by the caller, plus what the application itself needs or can make use of.
+    fn device_requirements(&self) -> DeviceRequirements {
+        let mut device_requirements = self.device_requirements.clone();
+        device_requirements.optional_features.core.samplerAnisotropy = vk::VK_TRUE;
+        device_requirements.formats.push(FormatRequirement {
+            format: TEXTURE_FORMAT,
+            features: vk::VK_FORMAT_FEATURE_SAMPLED_IMAGE_BIT as _
+        });
+        device_requirements.minimum_limits.push(MinimumLimit::new(
+            "maxImageDimension2D",
+            |limits| limits.maxImageDimension2D as f64,
+            self.texture.width.max(self.texture.height) as f64
+        ));
+
+        device_requirements
+    }
+
+
+    /// The extensions asked for by the caller, plus the swapchain extension when presenting to a window and what
+    /// the application can make use of.
+    fn device_extensions(&self) -> DeviceExtensions {
+        let mut device_extensions = self.device_extensions.clone();
+        if !self.headless {
+            device_extensions.require(SWAPCHAIN_EXTENSION).expect("the swapchain extension is known");
+        }
+        device_extensions.request(MEMORY_BUDGET_EXTENSION).expect("the memory budget extension is known");
+
+        device_extensions
+    }
+
+
+    /// Names of the extensions `device` offers. A failed query counts as offering none.
+    fn available_device_extensions(&self, device: vk::VkPhysicalDevice) -> Vec<String> {
         unsafe {
             let mut extension_count: u32 = 0;
-            vk::vkEnumerateDeviceExtensionProperties(device, null(), &mut extension_count, null_mut());
+            if vk::vkEnumerateDeviceExtensionProperties(device, null(), &mut extension_count, null_mut()) != vk::VK_SUCCESS {
+                return Vec::new();
+            }
             let mut available_extensions: Vec<vk::VkExtensionProperties> = Vec::with_capacity(extension_count as usize);
             vk::vkEnumerateDeviceExtensionProperties(device, null(), &mut extension_count, available_extensions.as_mut_ptr());
             available_extensions.set_len(extension_count as usize);
 
-            let mut required_extensions: HashSet<*const u8> = HashSet::from(DEVICE_EXTENSIONS);
-            for extension in available_extensions {
-                required_extensions.remove(&(extension.extensionName.as_ptr() as _));
-            }
-
-            !required_extensions.is_empty()
+            available_extensions.iter()
+                .map(|extension| std::ffi::CStr::from_ptr(extension.extensionName.as_ptr()).to_string_lossy().into_owned())
+                .collect()
         }
     }
 
@@ -878,18 +3006,18 @@ impl HelloTriangleApplication {
     fn query_swap_chain_support(&self, device: vk::VkPhysicalDevice) -> SwapChainSupportDetails {
         let mut details = SwapChainSupportDetails::default();
         unsafe {
-            vk::vkGetPhysicalDeviceSurfaceCapabilitiesKHR(device, self.surface, &mut details.capabilities);
+            vk::vkGetPhysicalDeviceSurfaceCapabilitiesKHR(device, self.surface.raw(), &mut details.capabilities);
             let mut format_count:u32 = 0;
-            vk::vkGetPhysicalDeviceSurfaceFormatsKHR(device, self.surface, &mut format_count, null_mut());
+            vk::vkGetPhysicalDeviceSurfaceFormatsKHR(device, self.surface.raw(), &mut format_count, null_mut());
             if format_count > 0 {
                 details.formats.resize(format_count as usize, vk::VkSurfaceFormatKHR::default());
-                vk::vkGetPhysicalDeviceSurfaceFormatsKHR(device, self.surface, &mut format_count, details.formats.as_mut_ptr());
+                vk::vkGetPhysicalDeviceSurfaceFormatsKHR(device, self.surface.raw(), &mut format_count, details.formats.as_mut_ptr());
             }
             let mut present_mode_count:u32 = 0;
-            vk::vkGetPhysicalDeviceSurfacePresentModesKHR(device, self.surface, &mut present_mode_count, null_mut());
+            vk::vkGetPhysicalDeviceSurfacePresentModesKHR(device, self.surface.raw(), &mut present_mode_count, null_mut());
             if present_mode_count > 0 {
                 details.present_modes.resize(present_mode_count as usize, vk::VkPresentModeKHR::default());
-                vk::vkGetPhysicalDeviceSurfacePresentModesKHR(device, self.surface, &mut present_mode_count, details.present_modes.as_mut_ptr());
+                vk::vkGetPhysicalDeviceSurfacePresentModesKHR(device, self.surface.raw(), &mut present_mode_count, details.present_modes.as_mut_ptr());
             }
         }
 
@@ -923,7 +3051,7 @@ impl HelloTriangleApplication {
             capabilities.currentExtent
         } else {
             let (mut width, mut height): (i32, i32) = (0, 0);
-            unsafe {glfwGetFramebufferSize(self.window, &mut width, &mut height)}
+            unsafe {glfwGetFramebufferSize(self.window.raw(), &mut width, &mut height)}
             vk::VkExtent2D {
                 width: (width as u32).clamp(capabilities.minImageExtent.width, capabilities.maxImageExtent.width),
                 height: (height as u32).clamp(capabilities.minImageExtent.height, capabilities.maxImageExtent.height)
@@ -932,28 +3060,83 @@ impl HelloTriangleApplication {
     }
 
 
-    fn create_shader_module(&self, code: &Vec<u8>) -> vk::VkShaderModule {
+    /// Checks the reflected ranges against the rules of `VkPipelineLayoutCreateInfo` and the device limit.
+    fn check_push_constant_ranges(&self, ranges: &[PushConstantRange]) -> Result<()> {
+        let mut device_properties = vk::VkPhysicalDeviceProperties::default();
+        unsafe{vk::vkGetPhysicalDeviceProperties(self.physical_device, &mut device_properties)};
+        let max_size = device_properties.limits.maxPushConstantsSize;
+
+        let mut used_stages: vk::VkShaderStageFlags = 0;
+        for range in ranges {
+            if range.size == 0 || range.offset % 4 != 0 || range.size % 4 != 0 {
+                return unsupported(Stage::Pipeline, &format!(
+                    "push constant range {}..{} is empty or not aligned to 4 bytes", range.offset, range.end()));
+            }
+            if range.end() > max_size {
+                return unsupported(Stage::Pipeline, &format!(
+                    "push constant range {}..{} exceeds the device limit of {} bytes", range.offset, range.end(), max_size));
+            }
+            if range.stages == 0 || range.stages & used_stages != 0 {
+                return unsupported(Stage::Pipeline, &format!(
+                    "push constant range {}..{} has no stages or shares a stage with another range", range.offset, range.end()));
+            }
+            used_stages |= range.stages;
+        }
+
+        Ok(())
+    }
+
+
+    /// `code` has been checked by `ShaderSource::load`, and is aligned as `pCode` requires.
+    fn create_shader_module(&self, code: &[u32]) -> Result<ShaderModule> {
         let mut create_info = vk::VkShaderModuleCreateInfo::default();
         create_info.sType = vk::VK_STRUCTURE_TYPE_SHADER_MODULE_CREATE_INFO;
-        create_info.codeSize = code.len();
-        create_info.pCode = code.as_ptr() as _;
+        create_info.codeSize = std::mem::size_of_val(code);
+        create_info.pCode = code.as_ptr();
         let mut shader_module = vk::VkShaderModule::default();
-        if unsafe{vk::vkCreateShaderModule(self.device, &create_info, null(), &mut shader_module)} != vk::VK_SUCCESS {
-            panic!("failed to create shader module");
-        }
+        check(
+            unsafe{vk::vkCreateShaderModule(self.device.raw(), &create_info, null(), &mut shader_module)},
+            Stage::Pipeline,
+            "failed to create shader module"
+        )?;
 
-        shader_module
+        Ok(ShaderModule::new(self.device.raw(), shader_module))
     }
 }
 
 
-fn read_file(filename: &str) -> Vec<u8> {
-    std::fs::read(filename).expect("failed to open file")
+fn supports_linear_filter(features: vk::VkFormatFeatureFlags) -> bool {
+    features & vk::VK_FORMAT_FEATURE_SAMPLED_IMAGE_FILTER_LINEAR_BIT as vk::VkFormatFeatureFlags != 0
 }
 
 
-const fn get_api_version(major: u32, minor: u32) -> u32 {
-    major << 22 | minor << 12
+/// How a buffer with `usage` is accessed after its upload, and the stages that do it. Buffers with none of the
+/// known uses are only handed over, without waiting for anything.
+fn buffer_access(usage: vk::VkBufferUsageFlags) -> (vk::VkAccessFlags, vk::VkPipelineStageFlags) {
+    let shader_stages = vk::VK_PIPELINE_STAGE_VERTEX_SHADER_BIT
+        | vk::VK_PIPELINE_STAGE_FRAGMENT_SHADER_BIT
+        | vk::VK_PIPELINE_STAGE_COMPUTE_SHADER_BIT;
+    let shader_read_write = vk::VK_ACCESS_SHADER_READ_BIT | vk::VK_ACCESS_SHADER_WRITE_BIT;
+    let uses = [
+        (vk::VK_BUFFER_USAGE_VERTEX_BUFFER_BIT, vk::VK_ACCESS_VERTEX_ATTRIBUTE_READ_BIT, vk::VK_PIPELINE_STAGE_VERTEX_INPUT_BIT),
+        (vk::VK_BUFFER_USAGE_INDEX_BUFFER_BIT, vk::VK_ACCESS_INDEX_READ_BIT, vk::VK_PIPELINE_STAGE_VERTEX_INPUT_BIT),
+        (vk::VK_BUFFER_USAGE_UNIFORM_BUFFER_BIT, vk::VK_ACCESS_UNIFORM_READ_BIT, shader_stages),
+        (vk::VK_BUFFER_USAGE_UNIFORM_TEXEL_BUFFER_BIT, vk::VK_ACCESS_SHADER_READ_BIT, shader_stages),
+        (vk::VK_BUFFER_USAGE_STORAGE_BUFFER_BIT, shader_read_write, shader_stages),
+        (vk::VK_BUFFER_USAGE_STORAGE_TEXEL_BUFFER_BIT, shader_read_write, shader_stages),
+        (vk::VK_BUFFER_USAGE_INDIRECT_BUFFER_BIT, vk::VK_ACCESS_INDIRECT_COMMAND_READ_BIT, vk::VK_PIPELINE_STAGE_DRAW_INDIRECT_BIT),
+        (vk::VK_BUFFER_USAGE_TRANSFER_SRC_BIT, vk::VK_ACCESS_TRANSFER_READ_BIT, vk::VK_PIPELINE_STAGE_TRANSFER_BIT)
+    ];
+
+    let (mut access, mut stage) = (0, 0);
+    for (bit, bit_access, bit_stage) in uses {
+        if usage & bit as vk::VkBufferUsageFlags != 0 {
+            access |= bit_access as vk::VkAccessFlags;
+            stage |= bit_stage as vk::VkPipelineStageFlags;
+        }
+    }
+
+    if stage == 0 {(0, vk::VK_PIPELINE_STAGE_BOTTOM_OF_PIPE_BIT as _)} else {(access, stage)}
 }
 
 
@@ -986,16 +3169,13 @@ fn create_debug_utils_messenger(
 }
 
 
-fn destroy_debug_utils_messenger(
-    instance: vk::VkInstance, 
-    p_debug_messenger: vk::VkDebugUtilsMessengerEXT,
-    p_allocator: *const vk::VkAllocationCallbacks,
-) {
-    let option_fn_ptr = unsafe{std::mem::transmute::<_, vk::PFN_vkDestroyDebugUtilsMessengerEXT>(
-        vk::vkGetInstanceProcAddr(instance, "vkDestroyDebugUtilsMessengerEXT".as_ptr() as _))};
-    if let Some(fn_ptr) = option_fn_ptr {
-        unsafe{fn_ptr(instance, p_debug_messenger, p_allocator)}
-    } 
+extern "C" fn framebuffer_resize_callback(window: *mut GLFWwindow, _width: i32, _height: i32) {
+    let app = unsafe{glfwGetWindowUserPointer(window)} as *mut HelloTriangleApplication;
+    // `run` holds a mutable borrow of the app while events are polled, so no reference may be created here,
+    // only the flag is written through the raw pointer.
+    if !app.is_null() {
+        unsafe{std::ptr::addr_of_mut!((*app).framebuffer_resized).write(true)};
+    }
 }
 
 
@@ -1013,17 +3193,56 @@ extern "C" fn debugCallback(
 }
 
 
-#[derive(Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct QueueFamilyIndices {
     graphics_family: Option<u32>,
-    present_family: Option<u32>
+    present_family: Option<u32>,
+    // families without graphics support, `None` where the device has none
+    transfer_family: Option<u32>,
+    compute_family: Option<u32>
 }
 
 impl QueueFamilyIndices {
+    /// The family uploads run on, falling back to the graphics family.
+    pub fn transfer_family(&self) -> Option<u32> {
+        self.transfer_family.or(self.graphics_family)
+    }
+
 
-    pub fn is_complete(&self) -> bool {
-        self.graphics_family.is_some() && self.present_family.is_some()
+    /// The family for asynchronous compute work, falling back to the graphics family.
+    pub fn compute_family(&self) -> Option<u32> {
+        self.compute_family.or(self.graphics_family)
     }
+
+
+    /// Every family a queue is created on, each once.
+    pub fn unique_families(&self) -> Vec<u32> {
+        let mut families: Vec<u32> = [self.graphics_family, self.present_family, self.transfer_family, self.compute_family]
+            .into_iter()
+            .flatten()
+            .collect();
+        families.sort();
+        families.dedup();
+
+        families
+    }
+}
+
+
+/// The queue a single use command buffer is submitted to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum QueueRole {
+    Graphics,
+    Transfer
+}
+
+
+/// A resource written on the transfer queue that is handed over to the graphics queue.
+#[derive(Clone, Copy)]
+enum Transferred<'a> {
+    Buffer(&'a Buffer),
+    /// All of its mip levels, in the transfer destination layout.
+    Image(&'a Image, u32)
 }
 
 
@@ -1032,4 +3251,20 @@ struct SwapChainSupportDetails {
     capabilities: vk::VkSurfaceCapabilitiesKHR,
     formats: Vec<vk::VkSurfaceFormatKHR>,
     present_modes: Vec<vk::VkPresentModeKHR>
+}
+
+
+/// Shader sources of the graphics pipeline.
+struct Shaders {
+    vertex: ShaderSource,
+    fragment: ShaderSource
+}
+
+impl Default for Shaders {
+    fn default() -> Self {
+        Self {
+            vertex: ShaderSource::Embedded("shader.vert"),
+            fragment: ShaderSource::Embedded("shader.frag")
+        }
+    }
 }
\ No newline at end of file
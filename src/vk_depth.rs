@@ -0,0 +1,109 @@
+use vulkan::vulkan as vk;
+
+/// Depth formats in order of preference. The first one supported as an optimal tiling depth attachment is used.
+pub const DEPTH_FORMAT_CANDIDATES: [vk::VkFormat; 3] = [
+    vk::VK_FORMAT_D32_SFLOAT,
+    vk::VK_FORMAT_D32_SFLOAT_S8_UINT,
+    vk::VK_FORMAT_D24_UNORM_S8_UINT
+];
+
+
+/// Depth test state of the graphics pipeline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DepthState {
+    pub test_enable: bool,
+    pub write_enable: bool,
+    /// How a fragment's depth is compared against the stored one, only used when testing is enabled.
+    pub compare_op: vk::VkCompareOp
+}
+
+impl DepthState {
+    /// Parses a compare op like `less-or-equal`, which tests and writes depth with it, or `off` to do neither.
+    pub fn parse(value: &str) -> Option<Self> {
+        let compare_op = match value {
+            "off" => return Some(Self {test_enable: false, write_enable: false, ..Default::default()}),
+            "never" => vk::VK_COMPARE_OP_NEVER,
+            "less" => vk::VK_COMPARE_OP_LESS,
+            "equal" => vk::VK_COMPARE_OP_EQUAL,
+            "less-or-equal" => vk::VK_COMPARE_OP_LESS_OR_EQUAL,
+            "greater" => vk::VK_COMPARE_OP_GREATER,
+            "not-equal" => vk::VK_COMPARE_OP_NOT_EQUAL,
+            "greater-or-equal" => vk::VK_COMPARE_OP_GREATER_OR_EQUAL,
+            "always" => vk::VK_COMPARE_OP_ALWAYS,
+            _ => return None
+        };
+
+        Some(Self {compare_op, ..Default::default()})
+    }
+
+
+    pub fn to_vk(&self) -> vk::VkPipelineDepthStencilStateCreateInfo {
+        let mut depth_stencil = vk::VkPipelineDepthStencilStateCreateInfo::default();
+        depth_stencil.sType = vk::VK_STRUCTURE_TYPE_PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO;
+        depth_stencil.depthTestEnable = if self.test_enable {vk::VK_TRUE} else {vk::VK_FALSE};
+        depth_stencil.depthWriteEnable = if self.write_enable {vk::VK_TRUE} else {vk::VK_FALSE};
+        depth_stencil.depthCompareOp = self.compare_op;
+        depth_stencil.depthBoundsTestEnable = vk::VK_FALSE;
+        depth_stencil.minDepthBounds = 0.0;
+        depth_stencil.maxDepthBounds = 1.0;
+        depth_stencil.stencilTestEnable = vk::VK_FALSE;
+
+        depth_stencil
+    }
+}
+
+/// Closer fragments win, with depth cleared to 1.0.
+impl Default for DepthState {
+    fn default() -> Self {
+        Self {test_enable: true, write_enable: true, compare_op: vk::VK_COMPARE_OP_LESS}
+    }
+}
+
+
+pub fn has_stencil_component(format: vk::VkFormat) -> bool {
+    format == vk::VK_FORMAT_D32_SFLOAT_S8_UINT || format == vk::VK_FORMAT_D24_UNORM_S8_UINT
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_ops_test_and_write_depth() {
+        let depth_state = DepthState::parse("greater-or-equal").unwrap();
+        assert_eq!(
+            depth_state,
+            DepthState {test_enable: true, write_enable: true, compare_op: vk::VK_COMPARE_OP_GREATER_OR_EQUAL}
+        );
+        assert_eq!(DepthState::parse("less"), Some(DepthState::default()));
+        assert_eq!(DepthState::parse("always").unwrap().compare_op, vk::VK_COMPARE_OP_ALWAYS);
+    }
+
+
+    #[test]
+    fn off_neither_tests_nor_writes() {
+        let depth_state = DepthState::parse("off").unwrap();
+        assert!(!depth_state.test_enable && !depth_state.write_enable);
+        let depth_stencil = depth_state.to_vk();
+        assert_eq!(depth_stencil.depthTestEnable, vk::VK_FALSE);
+        assert_eq!(depth_stencil.depthWriteEnable, vk::VK_FALSE);
+    }
+
+
+    #[test]
+    fn unknown_compare_ops_are_rejected() {
+        assert_eq!(DepthState::parse("LESS"), None);
+        assert_eq!(DepthState::parse("less_or_equal"), None);
+        assert_eq!(DepthState::parse(""), None);
+    }
+
+
+    #[test]
+    fn only_combined_formats_have_stencil() {
+        assert!(!has_stencil_component(vk::VK_FORMAT_D32_SFLOAT));
+        assert!(has_stencil_component(vk::VK_FORMAT_D32_SFLOAT_S8_UINT));
+        assert!(has_stencil_component(vk::VK_FORMAT_D24_UNORM_S8_UINT));
+        assert!(DEPTH_FORMAT_CANDIDATES.iter().any(|&format| !has_stencil_component(format)));
+    }
+}
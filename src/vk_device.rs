@@ -0,0 +1,681 @@
+use vulkan::vulkan as vk;
+use crate::vk_features::DeviceFeatures;
+use crate::vk_version;
+use std::fmt;
+
+/// Environment variable holding a `DeviceSelector`, see `DeviceSelector::parse`.
+pub const DEVICE_VAR: &str = "HELLO_TRIANGLE_DEVICE";
+
+/// Needed to present to a window, so it is required unless rendering offscreen.
+pub const SWAPCHAIN_EXTENSION: &str = "VK_KHR_swapchain";
+
+/// Reports how much of each memory heap the application may use, enabled where the device has it.
+pub const MEMORY_BUDGET_EXTENSION: &str = "VK_EXT_memory_budget";
+
+/// Lets draws begin rendering without a render pass object. Only of use with its feature, see `EXTENSION_FEATURES`.
+pub const DYNAMIC_RENDERING_EXTENSION: &str = "VK_KHR_dynamic_rendering";
+
+// the device extensions that can be asked for. Their dependencies are enabled along with them.
+const KNOWN_EXTENSIONS: [KnownExtension; 10] = [
+    KnownExtension {name: SWAPCHAIN_EXTENSION, dependencies: &[], instance_version: vk_version::VULKAN_1_0},
+    KnownExtension {
+        name: "VK_KHR_incremental_present",
+        dependencies: &[SWAPCHAIN_EXTENSION],
+        instance_version: vk_version::VULKAN_1_0
+    },
+    // depends on VK_KHR_get_physical_device_properties2, which the instance only has in core
+    KnownExtension {name: MEMORY_BUDGET_EXTENSION, dependencies: &[], instance_version: vk_version::VULKAN_1_1},
+    KnownExtension {name: "VK_KHR_push_descriptor", dependencies: &[], instance_version: vk_version::VULKAN_1_1},
+    KnownExtension {name: "VK_KHR_maintenance1", dependencies: &[], instance_version: vk_version::VULKAN_1_0},
+    KnownExtension {name: "VK_KHR_maintenance2", dependencies: &[], instance_version: vk_version::VULKAN_1_0},
+    KnownExtension {name: "VK_KHR_multiview", dependencies: &[], instance_version: vk_version::VULKAN_1_1},
+    KnownExtension {
+        name: "VK_KHR_create_renderpass2",
+        dependencies: &["VK_KHR_multiview", "VK_KHR_maintenance2"],
+        instance_version: vk_version::VULKAN_1_1
+    },
+    KnownExtension {
+        name: "VK_KHR_depth_stencil_resolve",
+        dependencies: &["VK_KHR_create_renderpass2"],
+        instance_version: vk_version::VULKAN_1_1
+    },
+    KnownExtension {
+        name: DYNAMIC_RENDERING_EXTENSION,
+        dependencies: &["VK_KHR_depth_stencil_resolve", "VK_KHR_create_renderpass2"],
+        instance_version: vk_version::VULKAN_1_1
+    }
+];
+
+// known extensions that were promoted to core, with the device version that did. They are satisfied without being
+// enabled on devices used at that version.
+const CORE_EXTENSIONS: [(&str, u32); 6] = [
+    ("VK_KHR_maintenance1", vk_version::VULKAN_1_1),
+    ("VK_KHR_maintenance2", vk_version::VULKAN_1_1),
+    ("VK_KHR_multiview", vk_version::VULKAN_1_1),
+    ("VK_KHR_create_renderpass2", vk_version::VULKAN_1_2),
+    ("VK_KHR_depth_stencil_resolve", vk_version::VULKAN_1_2),
+    (DYNAMIC_RENDERING_EXTENSION, vk_version::VULKAN_1_3)
+];
+
+// known extensions that do nothing unless a feature of theirs is enabled too, named as in `DeviceFeatures::missing`.
+// Devices without the feature count as lacking the extension.
+const EXTENSION_FEATURES: [(&str, &str); 1] = [
+    (DYNAMIC_RENDERING_EXTENSION, "vulkan13.dynamicRendering")
+];
+
+const VENDOR_ID_NVIDIA: u32 = 0x10de;
+
+/// Which physical device the application runs on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DeviceSelector {
+    /// The suitable device with the highest score. Of devices with equal scores the one enumerated first wins.
+    #[default]
+    Best,
+    /// The device at this position in enumeration order, as shown by `DeviceCandidate::index`.
+    Index(usize),
+    /// The first device whose name contains this, ignoring case.
+    Name(String),
+    /// The device with these PCI vendor and device IDs.
+    Id {vendor: u32, device: u32}
+}
+
+impl DeviceSelector {
+    /// Parses a position like `1`, hexadecimal IDs like `10de:2204`, or else a name substring like `geforce`.
+    pub fn parse(value: &str) -> Self {
+        if let Ok(index) = value.parse() {
+            return DeviceSelector::Index(index);
+        }
+        if let Some((vendor, device)) = value.split_once(':') {
+            if let (Ok(vendor), Ok(device)) = (u32::from_str_radix(vendor, 16), u32::from_str_radix(device, 16)) {
+                return DeviceSelector::Id {vendor, device};
+            }
+        }
+
+        DeviceSelector::Name(value.to_string())
+    }
+
+
+    pub fn matches(&self, candidate: &DeviceCandidate) -> bool {
+        match self {
+            DeviceSelector::Best => true,
+            DeviceSelector::Index(index) => candidate.index == *index,
+            DeviceSelector::Name(name) => candidate.name.to_lowercase().contains(&name.to_lowercase()),
+            DeviceSelector::Id {vendor, device} => candidate.vendor_id == *vendor && candidate.device_id == *device
+        }
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceSelector::Best => write!(f, "the best device"),
+            DeviceSelector::Index(index) => write!(f, "device {}", index),
+            DeviceSelector::Name(name) => write!(f, "a device named like \"{}\"", name),
+            DeviceSelector::Id {vendor, device} => write!(f, "device {:04x}:{:04x}", vendor, device)
+        }
+    }
+}
+
+
+struct KnownExtension {
+    name: &'static str,
+    /// Device extensions it depends on.
+    dependencies: &'static [&'static str],
+    /// The instance version its instance level dependencies are core in.
+    instance_version: u32
+}
+
+
+/// Device extensions asked for by name, each one out of `KNOWN_EXTENSIONS`. Devices without every required
+/// extension are rejected, optional ones are enabled where the device has them. The dependencies of an extension
+/// count as asked for along with it, unless the device version has them in core, and so do the features listed for
+/// it in `EXTENSION_FEATURES`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceExtensions {
+    required: Vec<&'static str>,
+    optional: Vec<&'static str>
+}
+
+impl DeviceExtensions {
+    /// Fails if `name` is not an extension the application knows how to enable.
+    pub fn require(&mut self, name: &str) -> Result<(), String> {
+        let name = known_extension(name)?.name;
+        if !self.required.contains(&name) {
+            self.required.push(name);
+        }
+
+        Ok(())
+    }
+
+
+    /// Fails if `name` is not an extension the application knows how to enable.
+    pub fn request(&mut self, name: &str) -> Result<(), String> {
+        let name = known_extension(name)?.name;
+        if !self.optional.contains(&name) {
+            self.optional.push(name);
+        }
+
+        Ok(())
+    }
+
+
+    /// Why the required extensions cannot be enabled on a device offering `available` and `features`, used at
+    /// `device_version` by an instance of `instance_version`, or `None` if they all can.
+    pub fn unmet(
+        &self,
+        available: &[String],
+        features: &DeviceFeatures,
+        instance_version: u32,
+        device_version: u32
+    ) -> Option<String> {
+        let mut missing = Vec::new();
+        for &required in &self.required {
+            let extensions = with_dependencies(required, device_version);
+            if let Some(version) = needed_instance_version(&extensions, instance_version) {
+                return Some(format!("{} needs a Vulkan {} instance", required, vk_version::name(version)));
+            }
+            let missing_features = extension_features(&extensions).missing(features);
+            if !missing_features.is_empty() {
+                return Some(format!("it lacks the features {} for {}", missing_features.join(", "), required));
+            }
+            for extension in extensions {
+                if !available.iter().any(|name| name == extension) && !missing.contains(&extension) {
+                    missing.push(extension);
+                }
+            }
+        }
+
+        if missing.is_empty() {None} else {Some(format!("it lacks the device extensions {}", missing.join(", ")))}
+    }
+
+
+    /// What to enable on a device the required extensions are not `unmet` on: the required extensions and the
+    /// optional ones it has with their features, with the dependencies of both, each once.
+    pub fn enabled(
+        &self,
+        available: &[String],
+        features: &DeviceFeatures,
+        instance_version: u32,
+        device_version: u32
+    ) -> Vec<String> {
+        let mut enabled: Vec<&str> = Vec::new();
+        let required = self.required.iter().map(|&required| with_dependencies(required, device_version));
+        let optional = self.optional.iter()
+            .map(|&optional| with_dependencies(optional, device_version))
+            .filter(|extensions| {
+                needed_instance_version(extensions, instance_version).is_none()
+                    && extensions.iter().all(|extension| available.iter().any(|name| name == extension))
+                    && extension_features(extensions).missing(features).is_empty()
+            });
+        for extensions in required.chain(optional) {
+            for extension in extensions {
+                if !enabled.contains(&extension) {
+                    enabled.push(extension);
+                }
+            }
+        }
+
+        enabled.into_iter().map(str::to_string).collect()
+    }
+}
+
+
+/// The features the `enabled` extensions need to be of use.
+pub fn extension_features(enabled: &[impl AsRef<str>]) -> DeviceFeatures {
+    let mut features = DeviceFeatures::default();
+    let needed = EXTENSION_FEATURES.iter().filter(|(name, _)| enabled.iter().any(|enabled| enabled.as_ref() == *name));
+    for (_, feature) in needed {
+        assert!(features.enable(feature), "{} is not a feature", feature);
+    }
+
+    features
+}
+
+
+fn known_extension(name: &str) -> Result<&'static KnownExtension, String> {
+    KNOWN_EXTENSIONS.iter()
+        .find(|extension| extension.name == name)
+        .ok_or_else(|| format!("{} is not a device extension the application can enable", name))
+}
+
+
+/// The instance version `extensions` need if `instance_version` is too old for them.
+fn needed_instance_version(extensions: &[&str], instance_version: u32) -> Option<u32> {
+    let needed = extensions.iter()
+        .filter_map(|extension| known_extension(extension).ok())
+        .map(|extension| extension.instance_version)
+        .max()?;
+    if needed > instance_version {Some(needed)} else {None}
+}
+
+
+/// `name` followed by the extensions it depends on, directly or not, leaving out those in core at `device_version`.
+fn with_dependencies(name: &'static str, device_version: u32) -> Vec<&'static str> {
+    let in_core = |name: &str| CORE_EXTENSIONS.iter().any(|&(core, version)| core == name && version <= device_version);
+    let mut extensions = vec![name];
+    let mut position = 0;
+    while position < extensions.len() {
+        let known = known_extension(extensions[position]);
+        for &dependency in known.map(|extension| extension.dependencies).unwrap_or_default() {
+            if !in_core(dependency) && !extensions.contains(&dependency) {
+                extensions.push(dependency);
+            }
+        }
+        position += 1;
+    }
+
+    extensions
+}
+
+
+/// One memory heap as `VK_EXT_memory_budget` reports it, all in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeapBudget {
+    pub size: vk::VkDeviceSize,
+    /// How much the application may allocate from the heap without degrading performance, including `usage`.
+    pub budget: vk::VkDeviceSize,
+    /// How much the application has allocated from the heap.
+    pub usage: vk::VkDeviceSize
+}
+
+
+/// Support a device needs for `format` with optimal tiling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatRequirement {
+    pub format: vk::VkFormat,
+    pub features: vk::VkFormatFeatureFlags
+}
+
+
+/// A lower bound on one member of `VkPhysicalDeviceLimits`, named after it in rejections.
+#[derive(Clone, Copy, Debug)]
+pub struct MinimumLimit {
+    pub name: &'static str,
+    pub value: fn(&vk::VkPhysicalDeviceLimits) -> f64,
+    pub minimum: f64
+}
+
+impl MinimumLimit {
+    /// e.g. `MinimumLimit::new("maxBoundDescriptorSets", |limits| limits.maxBoundDescriptorSets as f64, 4.0)`
+    pub fn new(name: &'static str, value: fn(&vk::VkPhysicalDeviceLimits) -> f64, minimum: f64) -> Self {
+        Self {name, value, minimum}
+    }
+}
+
+
+/// What a device has to offer to be chosen, and what the logical device enables on it.
+#[derive(Clone, Default)]
+pub struct DeviceRequirements {
+    /// Devices without all of these are rejected, and all of them are enabled.
+    pub required_features: DeviceFeatures,
+    /// Enabled where the device has them, and each one the device has raises its score.
+    pub optional_features: DeviceFeatures,
+    pub minimum_limits: Vec<MinimumLimit>,
+    pub formats: Vec<FormatRequirement>
+}
+
+impl DeviceRequirements {
+    /// Why a device with `features` and `limits` cannot be used, or `None` if it meets every requirement.
+    /// `format_features` gives the optimal tiling features of a format on the device.
+    pub fn unmet(
+        &self,
+        features: &DeviceFeatures,
+        limits: &vk::VkPhysicalDeviceLimits,
+        format_features: impl Fn(vk::VkFormat) -> vk::VkFormatFeatureFlags
+    ) -> Option<String> {
+        let missing_features = self.required_features.missing(features);
+        if !missing_features.is_empty() {
+            return Some(format!("it lacks the features {}", missing_features.join(", ")));
+        }
+
+        for limit in &self.minimum_limits {
+            let value = (limit.value)(limits);
+            if value < limit.minimum {
+                return Some(format!("its {} of {} is below {}", limit.name, value, limit.minimum));
+            }
+        }
+
+        for requirement in &self.formats {
+            if format_features(requirement.format) & requirement.features != requirement.features {
+                return Some(format!("it does not support format {} for the required uses", requirement.format));
+            }
+        }
+
+        None
+    }
+
+
+    /// The features to enable on a device offering `available`.
+    pub fn enabled_features(&self, available: &DeviceFeatures) -> DeviceFeatures {
+        self.required_features.union(&self.optional_features.intersection(available))
+    }
+}
+
+
+/// Whether a device can run the application.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Suitability {
+    /// Higher scores are preferred when no device is selected explicitly.
+    Suitable {score: u32},
+    /// Why the device cannot be used.
+    Rejected(String)
+}
+
+
+/// A physical device as seen by device selection.
+#[derive(Clone, Debug)]
+pub struct DeviceCandidate {
+    /// Position in enumeration order.
+    pub index: usize,
+    pub name: String,
+    pub device_type: vk::VkPhysicalDeviceType,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub driver_version: u32,
+    /// The newest Vulkan version the device supports, which the instance version may cap.
+    pub api_version: u32,
+    pub suitability: Suitability,
+    pub(crate) handle: vk::VkPhysicalDevice
+}
+
+impl DeviceCandidate {
+    pub fn type_name(&self) -> &'static str {
+        match self.device_type {
+            vk::VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU => "discrete gpu",
+            vk::VK_PHYSICAL_DEVICE_TYPE_INTEGRATED_GPU => "integrated gpu",
+            vk::VK_PHYSICAL_DEVICE_TYPE_VIRTUAL_GPU => "virtual gpu",
+            vk::VK_PHYSICAL_DEVICE_TYPE_CPU => "cpu",
+            _ => "other"
+        }
+    }
+
+
+    /// The driver version in the vendor's own notation, which only some drivers share with Vulkan versions.
+    pub fn driver_version_name(&self) -> String {
+        let version = self.driver_version;
+        if self.vendor_id == VENDOR_ID_NVIDIA {
+            format!("{}.{}.{}.{}", version >> 22, version >> 14 & 0xff, version >> 6 & 0xff, version & 0x3f)
+        } else {
+            format!("{}.{}.{}", version >> 22, version >> 12 & 0x3ff, version & 0xfff)
+        }
+    }
+}
+
+impl fmt::Display for DeviceCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {} ({}, {:04x}:{:04x}, vulkan {}, driver {}): ",
+            self.index,
+            self.name,
+            self.type_name(),
+            self.vendor_id,
+            self.device_id,
+            vk_version::name(self.api_version),
+            self.driver_version_name()
+        )?;
+        match &self.suitability {
+            Suitability::Suitable {score} => write!(f, "score {}", score),
+            Suitability::Rejected(reason) => write!(f, "rejected, {}", reason)
+        }
+    }
+}
+
+
+/// Picks the candidate `selector` asks for, the first suitable one if several match. Errors explain the choice,
+/// listing the matching candidates, or every candidate if none matches.
+pub fn select<'a>(candidates: &'a [DeviceCandidate], selector: &DeviceSelector) -> Result<&'a DeviceCandidate, String> {
+    let listing = |candidates: &[&DeviceCandidate]| {
+        candidates.iter().map(|candidate| format!("\n  {}", candidate)).collect::<String>()
+    };
+    let all: Vec<&DeviceCandidate> = candidates.iter().collect();
+
+    if *selector == DeviceSelector::Best {
+        // `max_by_key` returns the last of equal maxima, reversing makes ties go to the first device
+        let best = candidates.iter()
+            .rev()
+            .filter_map(|candidate| match candidate.suitability {
+                Suitability::Suitable {score} => Some((candidate, score)),
+                Suitability::Rejected(_) => None
+            })
+            .max_by_key(|(_, score)| *score);
+
+        return match best {
+            Some((candidate, _)) => Ok(candidate),
+            None => Err(format!("failed to find a suitable gpu:{}", listing(&all)))
+        };
+    }
+
+    let matching: Vec<&DeviceCandidate> = candidates.iter().filter(|candidate| selector.matches(candidate)).collect();
+    match matching.iter().copied().find(|candidate| matches!(candidate.suitability, Suitability::Suitable {..})) {
+        Some(candidate) => Ok(candidate),
+        None if matching.is_empty() => Err(format!("there is no {} among the gpus:{}", selector, listing(&all))),
+        None => Err(format!("{} was selected but cannot be used:{}", selector, listing(&matching)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(index: usize, name: &str, suitability: Suitability) -> DeviceCandidate {
+        DeviceCandidate {
+            index,
+            name: name.to_string(),
+            device_type: vk::VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU,
+            vendor_id: VENDOR_ID_NVIDIA,
+            device_id: 0x2200 + index as u32,
+            driver_version: 0,
+            api_version: vk_version::VULKAN_1_3,
+            suitability,
+            handle: std::ptr::null_mut()
+        }
+    }
+
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+
+    fn selected(candidates: &[DeviceCandidate], selector: &DeviceSelector) -> usize {
+        select(candidates, selector).unwrap().index
+    }
+
+
+    #[test]
+    fn selectors_parse_as_index_id_or_name() {
+        assert_eq!(DeviceSelector::parse("1"), DeviceSelector::Index(1));
+        assert_eq!(DeviceSelector::parse("10de:2204"), DeviceSelector::Id {vendor: 0x10de, device: 0x2204});
+        assert_eq!(DeviceSelector::parse("10DE:2204"), DeviceSelector::Id {vendor: 0x10de, device: 0x2204});
+        assert_eq!(DeviceSelector::parse("geforce"), DeviceSelector::Name("geforce".to_string()));
+        assert_eq!(DeviceSelector::parse("rtx:3090"), DeviceSelector::Name("rtx:3090".to_string()));
+    }
+
+
+    #[test]
+    fn best_breaks_ties_by_enumeration_order() {
+        let candidates = [
+            candidate(0, "llvmpipe", Suitability::Suitable {score: 10}),
+            candidate(1, "GeForce A", Suitability::Suitable {score: 1000}),
+            candidate(2, "GeForce B", Suitability::Suitable {score: 1000})
+        ];
+        assert_eq!(selected(&candidates, &DeviceSelector::Best), 1);
+    }
+
+
+    #[test]
+    fn best_skips_rejected_devices() {
+        let candidates = [
+            candidate(0, "GeForce", Suitability::Rejected("it lacks the features samplerAnisotropy".to_string())),
+            candidate(1, "llvmpipe", Suitability::Suitable {score: 10})
+        ];
+        assert_eq!(selected(&candidates, &DeviceSelector::Best), 1);
+
+        let candidates = [candidate(0, "GeForce", Suitability::Rejected("it lacks everything".to_string()))];
+        assert!(select(&candidates, &DeviceSelector::Best).is_err());
+    }
+
+
+    #[test]
+    fn index_and_id_select_exactly_that_device() {
+        let candidates = [
+            candidate(0, "GeForce A", Suitability::Suitable {score: 1000}),
+            candidate(1, "llvmpipe", Suitability::Suitable {score: 10})
+        ];
+        assert_eq!(selected(&candidates, &DeviceSelector::Index(1)), 1);
+        assert_eq!(selected(&candidates, &DeviceSelector::Id {vendor: VENDOR_ID_NVIDIA, device: 0x2201}), 1);
+        assert!(select(&candidates, &DeviceSelector::Index(2)).is_err());
+        assert!(select(&candidates, &DeviceSelector::Id {vendor: 0x1002, device: 0x2200}).is_err());
+    }
+
+
+    #[test]
+    fn names_select_the_first_suitable_match() {
+        let candidates = [
+            candidate(0, "GeForce A", Suitability::Rejected("it cannot present".to_string())),
+            candidate(1, "llvmpipe", Suitability::Suitable {score: 10}),
+            candidate(2, "GeForce B", Suitability::Suitable {score: 1000})
+        ];
+        assert_eq!(selected(&candidates, &DeviceSelector::Name("geforce".to_string())), 2);
+    }
+
+
+    #[test]
+    fn rejections_of_matching_devices_are_listed() {
+        let candidates = [
+            candidate(0, "GeForce A", Suitability::Rejected("it cannot present".to_string())),
+            candidate(1, "llvmpipe", Suitability::Suitable {score: 10})
+        ];
+        let error = select(&candidates, &DeviceSelector::Name("geforce".to_string())).unwrap_err();
+        assert!(error.contains("it cannot present"));
+        assert!(!error.contains("llvmpipe"));
+
+        let error = select(&candidates, &DeviceSelector::Name("radeon".to_string())).unwrap_err();
+        assert!(error.contains("GeForce A") && error.contains("llvmpipe"));
+    }
+
+
+    #[test]
+    fn unknown_extensions_are_rejected() {
+        let mut extensions = DeviceExtensions::default();
+        assert!(extensions.require("VK_EXT_not_a_real_extension").is_err());
+        assert!(extensions.request("VK_KHR_swapchain\0").is_err());
+        assert!(extensions.request(MEMORY_BUDGET_EXTENSION).is_ok());
+    }
+
+
+    #[test]
+    fn dependencies_are_enabled_unless_they_are_core() {
+        let mut extensions = DeviceExtensions::default();
+        extensions.require("VK_KHR_depth_stencil_resolve").unwrap();
+        let available = names(&[
+            "VK_KHR_depth_stencil_resolve", "VK_KHR_create_renderpass2", "VK_KHR_multiview", "VK_KHR_maintenance2"
+        ]);
+        let features = DeviceFeatures::default();
+
+        let (vulkan_1_1, vulkan_1_2) = (vk_version::VULKAN_1_1, vk_version::VULKAN_1_2);
+        assert_eq!(extensions.unmet(&available, &features, vulkan_1_1, vulkan_1_1), None);
+        assert_eq!(
+            extensions.enabled(&available, &features, vulkan_1_1, vulkan_1_1),
+            names(&["VK_KHR_depth_stencil_resolve", "VK_KHR_create_renderpass2"])
+        );
+        assert_eq!(
+            extensions.enabled(&available, &features, vulkan_1_2, vulkan_1_2),
+            names(&["VK_KHR_depth_stencil_resolve"])
+        );
+    }
+
+
+    #[test]
+    fn required_extensions_need_their_dependencies_and_instance_version() {
+        let mut extensions = DeviceExtensions::default();
+        extensions.require("VK_KHR_depth_stencil_resolve").unwrap();
+        let available = names(&["VK_KHR_depth_stencil_resolve"]);
+        let features = DeviceFeatures::default();
+
+        let vulkan_1_1 = vk_version::VULKAN_1_1;
+        let reason = extensions.unmet(&available, &features, vulkan_1_1, vulkan_1_1).unwrap();
+        assert!(reason.contains("VK_KHR_create_renderpass2"));
+        let reason = extensions.unmet(&available, &features, vk_version::VULKAN_1_0, vk_version::VULKAN_1_0).unwrap();
+        assert!(reason.contains("needs a Vulkan 1.1.0 instance"));
+    }
+
+
+    #[test]
+    fn optional_extensions_are_skipped_without_their_dependencies() {
+        let mut extensions = DeviceExtensions::default();
+        extensions.require(SWAPCHAIN_EXTENSION).unwrap();
+        extensions.request("VK_KHR_create_renderpass2").unwrap();
+        extensions.request(MEMORY_BUDGET_EXTENSION).unwrap();
+        let available = names(&[SWAPCHAIN_EXTENSION, "VK_KHR_create_renderpass2", MEMORY_BUDGET_EXTENSION]);
+        let features = DeviceFeatures::default();
+
+        let vulkan_1_0 = vk_version::VULKAN_1_0;
+        assert_eq!(extensions.enabled(&available, &features, vulkan_1_0, vulkan_1_0), names(&[SWAPCHAIN_EXTENSION]));
+        let vulkan_1_1 = vk_version::VULKAN_1_1;
+        assert_eq!(
+            extensions.enabled(&available, &features, vulkan_1_1, vulkan_1_1),
+            names(&[SWAPCHAIN_EXTENSION, "VK_KHR_create_renderpass2", MEMORY_BUDGET_EXTENSION])
+        );
+    }
+
+
+    #[test]
+    fn dynamic_rendering_needs_its_feature_and_dependencies_below_vulkan_1_3() {
+        let mut extensions = DeviceExtensions::default();
+        extensions.require(DYNAMIC_RENDERING_EXTENSION).unwrap();
+        let available = names(&[
+            DYNAMIC_RENDERING_EXTENSION, "VK_KHR_depth_stencil_resolve", "VK_KHR_create_renderpass2",
+            "VK_KHR_multiview", "VK_KHR_maintenance2"
+        ]);
+        let mut features = DeviceFeatures::default();
+
+        let (vulkan_1_1, vulkan_1_2) = (vk_version::VULKAN_1_1, vk_version::VULKAN_1_2);
+        let reason = extensions.unmet(&available, &features, vulkan_1_2, vulkan_1_2).unwrap();
+        assert!(reason.contains("vulkan13.dynamicRendering"));
+
+        features.enable("vulkan13.dynamicRendering");
+        assert_eq!(extensions.unmet(&available, &features, vulkan_1_1, vulkan_1_1), None);
+        let enabled = extensions.enabled(&available, &features, vulkan_1_1, vulkan_1_1);
+        assert_eq!(
+            enabled,
+            names(&[DYNAMIC_RENDERING_EXTENSION, "VK_KHR_depth_stencil_resolve", "VK_KHR_create_renderpass2"])
+        );
+        assert_eq!(extension_features(&enabled).names(), ["vulkan13.dynamicRendering"]);
+        let vulkan_1_3 = vk_version::VULKAN_1_3;
+        for version in [vulkan_1_2, vulkan_1_3] {
+            let enabled = extensions.enabled(&available, &features, version, version);
+            assert_eq!(enabled, names(&[DYNAMIC_RENDERING_EXTENSION]));
+        }
+
+        let mut optional = DeviceExtensions::default();
+        optional.request(DYNAMIC_RENDERING_EXTENSION).unwrap();
+        assert!(optional.enabled(&available, &DeviceFeatures::default(), vulkan_1_2, vulkan_1_2).is_empty());
+    }
+
+
+    #[test]
+    fn requirements_reject_missing_features_and_low_limits() {
+        let mut requirements = DeviceRequirements::default();
+        requirements.required_features.enable("vulkan12.timelineSemaphore");
+        requirements.minimum_limits.push(MinimumLimit::new(
+            "maxImageDimension2D",
+            |limits| limits.maxImageDimension2D as f64,
+            4096.0
+        ));
+        let mut features = DeviceFeatures::default();
+        let mut limits = vk::VkPhysicalDeviceLimits::default();
+        limits.maxImageDimension2D = 2048;
+        let format_features = |_| 0;
+
+        let reason = requirements.unmet(&features, &limits, format_features).unwrap();
+        assert!(reason.contains("vulkan12.timelineSemaphore"));
+        features.enable("vulkan12.timelineSemaphore");
+        let reason = requirements.unmet(&features, &limits, format_features).unwrap();
+        assert!(reason.contains("maxImageDimension2D"));
+        limits.maxImageDimension2D = 4096;
+        assert_eq!(requirements.unmet(&features, &limits, format_features), None);
+    }
+}
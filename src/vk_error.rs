@@ -0,0 +1,223 @@
+use vulkan::vulkan as vk;
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, AppError>;
+
+
+/// The step of setting up or running the application that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Window,
+    Instance,
+    DebugMessenger,
+    Surface,
+    PhysicalDevice,
+    LogicalDevice,
+    Swapchain,
+    ImageViews,
+    RenderPass,
+    Pipeline,
+    Descriptors,
+    Mesh,
+    Textures,
+    Shaders,
+    Framebuffers,
+    CommandPool,
+    CommandBuffers,
+    SyncObjects,
+    Memory,
+    Draw,
+    Present,
+    Readback,
+    Output,
+    Arguments
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Window => "window creation",
+            Stage::Instance => "instance creation",
+            Stage::DebugMessenger => "debug messenger setup",
+            Stage::Surface => "surface creation",
+            Stage::PhysicalDevice => "physical device selection",
+            Stage::LogicalDevice => "logical device creation",
+            Stage::Swapchain => "swapchain creation",
+            Stage::ImageViews => "image view creation",
+            Stage::RenderPass => "render pass creation",
+            Stage::Pipeline => "pipeline creation",
+            Stage::Descriptors => "descriptor setup",
+            Stage::Mesh => "mesh validation",
+            Stage::Textures => "texture upload",
+            Stage::Shaders => "shader loading",
+            Stage::Framebuffers => "framebuffer creation",
+            Stage::CommandPool => "command pool creation",
+            Stage::CommandBuffers => "command buffer recording",
+            Stage::SyncObjects => "synchronization object creation",
+            Stage::Memory => "memory allocation",
+            Stage::Draw => "drawing",
+            Stage::Present => "presentation",
+            Stage::Readback => "image readback",
+            Stage::Output => "image output",
+            Stage::Arguments => "argument parsing"
+        })
+    }
+}
+
+
+pub enum AppError {
+    /// A Vulkan call returned something other than `VK_SUCCESS`.
+    Vulkan {
+        stage: Stage,
+        result: vk::VkResult,
+        context: String
+    },
+    /// A file could not be read or decoded.
+    Io {
+        stage: Stage,
+        path: String,
+        source: std::io::Error
+    },
+    /// A shader did not compile. `log` holds the compiler's messages, which name the file and line.
+    Compile {
+        stage: Stage,
+        path: String,
+        log: String
+    },
+    /// A shader module is not well-formed SPIR-V. `reason` says what is wrong with it.
+    InvalidSpirv {
+        stage: Stage,
+        path: String,
+        reason: String
+    },
+    /// The instance or device lacks something the application needs.
+    Unsupported {
+        stage: Stage,
+        context: String
+    },
+    /// A file could not be encoded or written.
+    Write {
+        stage: Stage,
+        path: String,
+        source: std::io::Error
+    },
+    /// A command line flag or environment variable has a value that cannot be used. `name` is the flag or variable.
+    InvalidArgument {
+        name: String,
+        value: String,
+        expected: String
+    }
+}
+
+impl AppError {
+    pub fn stage(&self) -> Stage {
+        match self {
+            AppError::Vulkan {stage, ..}
+            | AppError::Io {stage, ..}
+            | AppError::Compile {stage, ..}
+            | AppError::InvalidSpirv {stage, ..}
+            | AppError::Unsupported {stage, ..}
+            | AppError::Write {stage, ..} => *stage,
+            AppError::InvalidArgument {..} => Stage::Arguments
+        }
+    }
+
+
+    /// The Vulkan result code, if the error came from a Vulkan call.
+    pub fn result(&self) -> Option<vk::VkResult> {
+        match self {
+            AppError::Vulkan {result, ..} => Some(*result),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Vulkan {stage, result, context} =>
+                write!(f, "{stage} failed: {context} ({})", result_name(*result)),
+            AppError::Io {stage, path, source} =>
+                write!(f, "{stage} failed: could not read {path}: {source}"),
+            AppError::Compile {stage, path, log} =>
+                write!(f, "{stage} failed: could not compile {path}:\n{log}"),
+            AppError::InvalidSpirv {stage, path, reason} =>
+                write!(f, "{stage} failed: {path} is not valid SPIR-V: {reason}"),
+            AppError::Unsupported {stage, context} =>
+                write!(f, "{stage} failed: {context}"),
+            AppError::Write {stage, path, source} =>
+                write!(f, "{stage} failed: could not write {path}: {source}"),
+            AppError::InvalidArgument {name, value, expected} =>
+                write!(f, "{} failed: invalid value `{value}` for {name}, expected {expected}", Stage::Arguments)
+        }
+    }
+}
+
+// `main` returns the error, and the Debug output is what gets printed then
+impl fmt::Debug for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Io {source, ..} | AppError::Write {source, ..} => Some(source),
+            _ => None
+        }
+    }
+}
+
+
+/// Turns a non-`VK_SUCCESS` result into an `AppError::Vulkan`.
+pub fn check(result: vk::VkResult, stage: Stage, context: &str) -> Result<()> {
+    if result == vk::VK_SUCCESS {
+        Ok(())
+    } else {
+        Err(AppError::Vulkan {stage, result, context: context.to_string()})
+    }
+}
+
+
+pub fn unsupported<T>(stage: Stage, context: &str) -> Result<T> {
+    Err(AppError::Unsupported {stage, context: context.to_string()})
+}
+
+
+pub fn result_name(result: vk::VkResult) -> &'static str {
+    match result {
+        vk::VK_SUCCESS => "VK_SUCCESS",
+        vk::VK_NOT_READY => "VK_NOT_READY",
+        vk::VK_TIMEOUT => "VK_TIMEOUT",
+        vk::VK_EVENT_SET => "VK_EVENT_SET",
+        vk::VK_EVENT_RESET => "VK_EVENT_RESET",
+        vk::VK_INCOMPLETE => "VK_INCOMPLETE",
+        vk::VK_SUBOPTIMAL_KHR => "VK_SUBOPTIMAL_KHR",
+        vk::VK_ERROR_OUT_OF_HOST_MEMORY => "VK_ERROR_OUT_OF_HOST_MEMORY",
+        vk::VK_ERROR_OUT_OF_DEVICE_MEMORY => "VK_ERROR_OUT_OF_DEVICE_MEMORY",
+        vk::VK_ERROR_INITIALIZATION_FAILED => "VK_ERROR_INITIALIZATION_FAILED",
+        vk::VK_ERROR_DEVICE_LOST => "VK_ERROR_DEVICE_LOST",
+        vk::VK_ERROR_MEMORY_MAP_FAILED => "VK_ERROR_MEMORY_MAP_FAILED",
+        vk::VK_ERROR_LAYER_NOT_PRESENT => "VK_ERROR_LAYER_NOT_PRESENT",
+        vk::VK_ERROR_EXTENSION_NOT_PRESENT => "VK_ERROR_EXTENSION_NOT_PRESENT",
+        vk::VK_ERROR_FEATURE_NOT_PRESENT => "VK_ERROR_FEATURE_NOT_PRESENT",
+        vk::VK_ERROR_INCOMPATIBLE_DRIVER => "VK_ERROR_INCOMPATIBLE_DRIVER",
+        vk::VK_ERROR_TOO_MANY_OBJECTS => "VK_ERROR_TOO_MANY_OBJECTS",
+        vk::VK_ERROR_FORMAT_NOT_SUPPORTED => "VK_ERROR_FORMAT_NOT_SUPPORTED",
+        vk::VK_ERROR_FRAGMENTED_POOL => "VK_ERROR_FRAGMENTED_POOL",
+        vk::VK_ERROR_UNKNOWN => "VK_ERROR_UNKNOWN",
+        vk::VK_ERROR_OUT_OF_POOL_MEMORY => "VK_ERROR_OUT_OF_POOL_MEMORY",
+        vk::VK_ERROR_INVALID_EXTERNAL_HANDLE => "VK_ERROR_INVALID_EXTERNAL_HANDLE",
+        vk::VK_ERROR_FRAGMENTATION => "VK_ERROR_FRAGMENTATION",
+        vk::VK_ERROR_INVALID_OPAQUE_CAPTURE_ADDRESS => "VK_ERROR_INVALID_OPAQUE_CAPTURE_ADDRESS",
+        vk::VK_PIPELINE_COMPILE_REQUIRED => "VK_PIPELINE_COMPILE_REQUIRED",
+        vk::VK_ERROR_SURFACE_LOST_KHR => "VK_ERROR_SURFACE_LOST_KHR",
+        vk::VK_ERROR_NATIVE_WINDOW_IN_USE_KHR => "VK_ERROR_NATIVE_WINDOW_IN_USE_KHR",
+        vk::VK_ERROR_OUT_OF_DATE_KHR => "VK_ERROR_OUT_OF_DATE_KHR",
+        vk::VK_ERROR_INCOMPATIBLE_DISPLAY_KHR => "VK_ERROR_INCOMPATIBLE_DISPLAY_KHR",
+        vk::VK_ERROR_VALIDATION_FAILED_EXT => "VK_ERROR_VALIDATION_FAILED_EXT",
+        vk::VK_ERROR_INVALID_SHADER_NV => "VK_ERROR_INVALID_SHADER_NV",
+        _ => "unknown VkResult"
+    }
+}
\ No newline at end of file
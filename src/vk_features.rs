@@ -0,0 +1,349 @@
+use vulkan::vulkan as vk;
+use crate::vk_device::DYNAMIC_RENDERING_EXTENSION;
+use crate::vk_version::{VULKAN_1_1, VULKAN_1_2, VULKAN_1_3};
+use std::mem::{align_of, offset_of, size_of};
+use std::ptr::null_mut;
+
+// member names in declaration order, every member of the structs is a VkBool32
+const CORE_FEATURE_NAMES: [&str; 55] = [
+    "robustBufferAccess", "fullDrawIndexUint32", "imageCubeArray", "independentBlend", "geometryShader",
+    "tessellationShader", "sampleRateShading", "dualSrcBlend", "logicOp", "multiDrawIndirect",
+    "drawIndirectFirstInstance", "depthClamp", "depthBiasClamp", "fillModeNonSolid", "depthBounds", "wideLines",
+    "largePoints", "alphaToOne", "multiViewport", "samplerAnisotropy", "textureCompressionETC2",
+    "textureCompressionASTC_LDR", "textureCompressionBC", "occlusionQueryPrecise", "pipelineStatisticsQuery",
+    "vertexPipelineStoresAndAtomics", "fragmentStoresAndAtomics", "shaderTessellationAndGeometryPointSize",
+    "shaderImageGatherExtended", "shaderStorageImageExtendedFormats", "shaderStorageImageMultisample",
+    "shaderStorageImageReadWithoutFormat", "shaderStorageImageWriteWithoutFormat",
+    "shaderUniformBufferArrayDynamicIndexing", "shaderSampledImageArrayDynamicIndexing",
+    "shaderStorageBufferArrayDynamicIndexing", "shaderStorageImageArrayDynamicIndexing", "shaderClipDistance",
+    "shaderCullDistance", "shaderFloat64", "shaderInt64", "shaderInt16", "shaderResourceResidency",
+    "shaderResourceMinLod", "sparseBinding", "sparseResidencyBuffer", "sparseResidencyImage2D",
+    "sparseResidencyImage3D", "sparseResidency2Samples", "sparseResidency4Samples", "sparseResidency8Samples",
+    "sparseResidency16Samples", "sparseResidencyAliased", "variableMultisampleRate", "inheritedQueries"
+];
+
+const VULKAN_11_FEATURE_NAMES: [&str; 12] = [
+    "storageBuffer16BitAccess", "uniformAndStorageBuffer16BitAccess", "storagePushConstant16",
+    "storageInputOutput16", "multiview", "multiviewGeometryShader", "multiviewTessellationShader",
+    "variablePointersStorageBuffer", "variablePointers", "protectedMemory", "samplerYcbcrConversion",
+    "shaderDrawParameters"
+];
+
+const VULKAN_12_FEATURE_NAMES: [&str; 47] = [
+    "samplerMirrorClampToEdge", "drawIndirectCount", "storageBuffer8BitAccess", "uniformAndStorageBuffer8BitAccess",
+    "storagePushConstant8", "shaderBufferInt64Atomics", "shaderSharedInt64Atomics", "shaderFloat16", "shaderInt8",
+    "descriptorIndexing", "shaderInputAttachmentArrayDynamicIndexing",
+    "shaderUniformTexelBufferArrayDynamicIndexing", "shaderStorageTexelBufferArrayDynamicIndexing",
+    "shaderUniformBufferArrayNonUniformIndexing", "shaderSampledImageArrayNonUniformIndexing",
+    "shaderStorageBufferArrayNonUniformIndexing", "shaderStorageImageArrayNonUniformIndexing",
+    "shaderInputAttachmentArrayNonUniformIndexing", "shaderUniformTexelBufferArrayNonUniformIndexing",
+    "shaderStorageTexelBufferArrayNonUniformIndexing", "descriptorBindingUniformBufferUpdateAfterBind",
+    "descriptorBindingSampledImageUpdateAfterBind", "descriptorBindingStorageImageUpdateAfterBind",
+    "descriptorBindingStorageBufferUpdateAfterBind", "descriptorBindingUniformTexelBufferUpdateAfterBind",
+    "descriptorBindingStorageTexelBufferUpdateAfterBind", "descriptorBindingUpdateUnusedWhilePending",
+    "descriptorBindingPartiallyBound", "descriptorBindingVariableDescriptorCount", "runtimeDescriptorArray",
+    "samplerFilterMinmax", "scalarBlockLayout", "imagelessFramebuffer", "uniformBufferStandardLayout",
+    "shaderSubgroupExtendedTypes", "separateDepthStencilLayouts", "hostQueryReset", "timelineSemaphore",
+    "bufferDeviceAddress", "bufferDeviceAddressCaptureReplay", "bufferDeviceAddressMultiDevice",
+    "vulkanMemoryModel", "vulkanMemoryModelDeviceScope", "vulkanMemoryModelAvailabilityVisibilityChains",
+    "shaderOutputViewportIndex", "shaderOutputLayer", "subgroupBroadcastDynamicId"
+];
+
+const VULKAN_13_FEATURE_NAMES: [&str; 15] = [
+    "robustImageAccess", "inlineUniformBlock", "descriptorBindingInlineUniformBlockUpdateAfterBind",
+    "pipelineCreationCacheControl", "privateData", "shaderDemoteToHelperInvocation", "shaderTerminateInvocation",
+    "subgroupSizeControl", "computeFullSubgroups", "synchronization2", "textureCompressionASTC_HDR",
+    "shaderZeroInitializeWorkgroupVariableMemory", "dynamicRendering", "shaderIntegerDotProduct", "maintenance4"
+];
+
+// the chained structs start with sType and pNext, their features follow
+const VULKAN_11_FEATURES_START: usize = offset_of!(vk::VkPhysicalDeviceVulkan11Features, storageBuffer16BitAccess);
+const VULKAN_12_FEATURES_START: usize = offset_of!(vk::VkPhysicalDeviceVulkan12Features, samplerMirrorClampToEdge);
+const VULKAN_13_FEATURES_START: usize = offset_of!(vk::VkPhysicalDeviceVulkan13Features, robustImageAccess);
+
+// a name table that misses a member would attribute every later feature to the wrong name
+const _: () = assert!(size_of::<vk::VkPhysicalDeviceFeatures>() == CORE_FEATURE_NAMES.len() * 4);
+const _: () = assert!(
+    size_of::<vk::VkPhysicalDeviceVulkan11Features>() == chained_size(VULKAN_11_FEATURES_START, VULKAN_11_FEATURE_NAMES.len())
+);
+const _: () = assert!(
+    size_of::<vk::VkPhysicalDeviceVulkan12Features>() == chained_size(VULKAN_12_FEATURES_START, VULKAN_12_FEATURE_NAMES.len())
+);
+const _: () = assert!(
+    size_of::<vk::VkPhysicalDeviceVulkan13Features>() == chained_size(VULKAN_13_FEATURES_START, VULKAN_13_FEATURE_NAMES.len())
+);
+
+
+/// Device features: those of `VkPhysicalDeviceFeatures` and of the structs Vulkan 1.2 and 1.3 chain behind it.
+///
+/// A set feature means required, supported or enabled depending on where the set is used. The chained structs
+/// are only queried and enabled on devices of the version that introduced them, `VkPhysicalDeviceVulkan11Features`
+/// and `VkPhysicalDeviceVulkan12Features` with 1.2 and `VkPhysicalDeviceVulkan13Features` with 1.3. Below 1.3,
+/// `vulkan13.dynamicRendering` stands for the feature of `VK_KHR_dynamic_rendering` on devices with that extension.
+#[derive(Clone, Copy, Default)]
+pub struct DeviceFeatures {
+    pub core: vk::VkPhysicalDeviceFeatures,
+    pub vulkan11: vk::VkPhysicalDeviceVulkan11Features,
+    pub vulkan12: vk::VkPhysicalDeviceVulkan12Features,
+    pub vulkan13: vk::VkPhysicalDeviceVulkan13Features,
+    // carries `vulkan13.dynamicRendering` through the chain below 1.3
+    dynamic_rendering: vk::VkPhysicalDeviceDynamicRenderingFeatures
+}
+
+impl DeviceFeatures {
+    /// The features of `device`, as far as `api_version` and the device `extensions` it offers let them be queried.
+    /// Below Vulkan 1.1 only the core features are read, and the chained structs of newer versions stay unset.
+    /// Vulkan 1.0 loaders do not have `vkGetPhysicalDeviceFeatures2`, so it is looked up through `instance` instead
+    /// of linked.
+    pub fn query(
+        instance: vk::VkInstance,
+        device: vk::VkPhysicalDevice,
+        api_version: u32,
+        extensions: &[String]
+    ) -> Self {
+        let mut features = Self::default();
+        let option_fn_ptr = if api_version < VULKAN_1_1 {None} else {
+            unsafe{std::mem::transmute::<_, vk::PFN_vkGetPhysicalDeviceFeatures2>(
+                vk::vkGetInstanceProcAddr(instance, "vkGetPhysicalDeviceFeatures2\0".as_ptr() as _))}
+        };
+        let Some(fn_ptr) = option_fn_ptr else {
+            unsafe{vk::vkGetPhysicalDeviceFeatures(device, &mut features.core)};
+            return features;
+        };
+
+        let mut features2 = features.chain(api_version, extensions);
+        unsafe{fn_ptr(device, &mut features2)};
+        features.core = features2.features;
+        if api_version < VULKAN_1_3 {
+            features.vulkan13.dynamicRendering = features.dynamic_rendering.dynamicRendering;
+        }
+
+        features
+    }
+
+
+    /// A `VkPhysicalDeviceFeatures2` holding the core features, with the structs `api_version` and the device
+    /// `extensions` have chained behind it. The chain points into `self`, which must stay in place while it is used.
+    pub fn chain(&mut self, api_version: u32, extensions: &[String]) -> vk::VkPhysicalDeviceFeatures2 {
+        self.vulkan11.sType = vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_VULKAN_1_1_FEATURES;
+        self.vulkan12.sType = vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_VULKAN_1_2_FEATURES;
+        self.vulkan13.sType = vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_VULKAN_1_3_FEATURES;
+        self.dynamic_rendering.sType = vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_DYNAMIC_RENDERING_FEATURES;
+        self.vulkan11.pNext = null_mut();
+        self.vulkan12.pNext = null_mut();
+        self.vulkan13.pNext = null_mut();
+        self.dynamic_rendering.pNext = null_mut();
+        self.dynamic_rendering.dynamicRendering = self.vulkan13.dynamicRendering;
+
+        let mut features2 = vk::VkPhysicalDeviceFeatures2::default();
+        features2.sType = vk::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_FEATURES_2;
+        features2.features = self.core;
+        features2.pNext = null_mut();
+        if api_version >= VULKAN_1_2 {
+            features2.pNext = &mut self.vulkan11 as *mut _ as _;
+            self.vulkan11.pNext = &mut self.vulkan12 as *mut _ as _;
+        }
+        if api_version >= VULKAN_1_3 {
+            self.vulkan12.pNext = &mut self.vulkan13 as *mut _ as _;
+        } else if extensions.iter().any(|extension| extension == DYNAMIC_RENDERING_EXTENSION) {
+            let last = if api_version >= VULKAN_1_2 {&mut self.vulkan12.pNext} else {&mut features2.pNext};
+            *last = &mut self.dynamic_rendering as *mut _ as _;
+        }
+
+        features2
+    }
+
+
+    /// Whether a feature of the chained structs is set, which takes a `VkPhysicalDeviceFeatures2` to enable.
+    pub fn has_chained(&self) -> bool {
+        (1..4).any(|group| self.flags(group).iter().any(|&flag| flag == vk::VK_TRUE))
+    }
+
+
+    /// The features set in `self` or `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a || b)
+    }
+
+
+    /// The features set in both `self` and `other`.
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a && b)
+    }
+
+
+    /// Names of the features set here but not in `available`. Features of the chained structs are prefixed with
+    /// their version, as in `vulkan12.timelineSemaphore`.
+    pub fn missing(&self, available: &Self) -> Vec<String> {
+        let mut missing = Vec::new();
+        for group in 0..4 {
+            let (prefix, names) = GROUPS[group];
+            for ((name, &flag), &available) in names.iter().zip(self.flags(group)).zip(available.flags(group)) {
+                if flag == vk::VK_TRUE && available != vk::VK_TRUE {
+                    missing.push(format!("{}{}", prefix, name));
+                }
+            }
+        }
+
+        missing
+    }
+
+
+    /// Names of the features set here, in the notation of `missing`.
+    pub fn names(&self) -> Vec<String> {
+        self.missing(&Self::default())
+    }
+
+
+    /// Sets the feature `name`, given in the notation of `missing`. Returns whether there is a feature of that name.
+    pub fn enable(&mut self, name: &str) -> bool {
+        for group in 0..4 {
+            let (prefix, names) = GROUPS[group];
+            let Some(member) = name.strip_prefix(prefix) else {continue};
+            if let Some(index) = names.iter().position(|&feature| feature == member) {
+                self.flags_mut(group)[index] = vk::VK_TRUE;
+                return true;
+            }
+        }
+
+        false
+    }
+
+
+    pub fn count(&self) -> usize {
+        (0..4).map(|group| self.flags(group).iter().filter(|&&flag| flag == vk::VK_TRUE).count()).sum()
+    }
+
+
+    fn combine(&self, other: &Self, operation: impl Fn(bool, bool) -> bool) -> Self {
+        let mut combined = Self::default();
+        for group in 0..4 {
+            let flags: Vec<vk::VkBool32> = self.flags(group).iter().zip(other.flags(group))
+                .map(|(&a, &b)| if operation(a == vk::VK_TRUE, b == vk::VK_TRUE) {vk::VK_TRUE} else {vk::VK_FALSE})
+                .collect();
+            combined.flags_mut(group).copy_from_slice(&flags);
+        }
+
+        combined
+    }
+
+
+    /// The features of one struct, 0 being the core ones and 1 to 3 the Vulkan 1.1 to 1.3 ones.
+    fn flags(&self, group: usize) -> &[vk::VkBool32] {
+        let (offset, count) = group_layout(group);
+        unsafe{std::slice::from_raw_parts((self as *const Self as *const u8).add(offset) as *const vk::VkBool32, count)}
+    }
+
+
+    fn flags_mut(&mut self, group: usize) -> &mut [vk::VkBool32] {
+        let (offset, count) = group_layout(group);
+        unsafe{std::slice::from_raw_parts_mut((self as *mut Self as *mut u8).add(offset) as *mut vk::VkBool32, count)}
+    }
+}
+
+
+// name prefix and member names of each group of `DeviceFeatures::flags`
+const GROUPS: [(&str, &[&str]); 4] = [
+    ("", &CORE_FEATURE_NAMES),
+    ("vulkan11.", &VULKAN_11_FEATURE_NAMES),
+    ("vulkan12.", &VULKAN_12_FEATURE_NAMES),
+    ("vulkan13.", &VULKAN_13_FEATURE_NAMES)
+];
+
+
+/// Offset of the first feature of a group within `DeviceFeatures` and the number of features in it.
+fn group_layout(group: usize) -> (usize, usize) {
+    let offset = match group {
+        0 => offset_of!(DeviceFeatures, core),
+        1 => offset_of!(DeviceFeatures, vulkan11) + VULKAN_11_FEATURES_START,
+        2 => offset_of!(DeviceFeatures, vulkan12) + VULKAN_12_FEATURES_START,
+        _ => offset_of!(DeviceFeatures, vulkan13) + VULKAN_13_FEATURES_START
+    };
+
+    (offset, GROUPS[group].1.len())
+}
+
+
+/// Size of a chained struct whose `count` features begin at `start`, padded to the alignment of its pNext.
+const fn chained_size(start: usize, count: usize) -> usize {
+    let align = align_of::<*mut std::ffi::c_void>();
+    (start + count * 4 + align - 1) / align * align
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(names: &[&str]) -> DeviceFeatures {
+        let mut features = DeviceFeatures::default();
+        for name in names {
+            assert!(features.enable(name), "{} is not a feature", name);
+        }
+        features
+    }
+
+
+    #[test]
+    fn names_round_trip() {
+        let names = ["samplerAnisotropy", "vulkan11.multiview", "vulkan12.timelineSemaphore", "vulkan13.maintenance4"];
+        let features = features(&names);
+        assert_eq!(features.core.samplerAnisotropy, vk::VK_TRUE);
+        assert_eq!(features.vulkan12.timelineSemaphore, vk::VK_TRUE);
+        assert_eq!(features.names(), names);
+        assert_eq!(features.count(), 4);
+        assert!(features.has_chained());
+    }
+
+
+    #[test]
+    fn unknown_names_are_not_enabled() {
+        let mut features = DeviceFeatures::default();
+        assert!(!features.enable("timelineSemaphore"));
+        assert!(!features.enable("vulkan13.samplerAnisotropy"));
+        assert!(!features.enable("vulkan12."));
+        assert_eq!(features.count(), 0);
+    }
+
+
+    #[test]
+    fn missing_names_what_is_not_available() {
+        let required = features(&["samplerAnisotropy", "geometryShader", "vulkan12.timelineSemaphore"]);
+        let available = features(&["geometryShader", "vulkan12.bufferDeviceAddress"]);
+        assert_eq!(required.missing(&available), ["samplerAnisotropy", "vulkan12.timelineSemaphore"]);
+        assert!(required.missing(&required).is_empty());
+    }
+
+
+    #[test]
+    fn union_and_intersection_combine_every_group() {
+        let a = features(&["samplerAnisotropy", "vulkan12.timelineSemaphore"]);
+        let b = features(&["samplerAnisotropy", "vulkan13.dynamicRendering"]);
+        let union = ["samplerAnisotropy", "vulkan12.timelineSemaphore", "vulkan13.dynamicRendering"];
+        assert_eq!(a.union(&b).names(), union);
+        assert_eq!(a.intersection(&b).names(), ["samplerAnisotropy"]);
+        assert!(!a.intersection(&b).has_chained());
+    }
+
+
+    #[test]
+    fn dynamic_rendering_is_chained_from_its_extension_below_vulkan_1_3() {
+        let extensions = [DYNAMIC_RENDERING_EXTENSION.to_string()];
+        let mut features = features(&["vulkan13.dynamicRendering"]);
+
+        let features2 = features.chain(VULKAN_1_2, &extensions);
+        assert_eq!(features2.pNext, &mut features.vulkan11 as *mut _ as _);
+        assert_eq!(features.vulkan12.pNext, &mut features.dynamic_rendering as *mut _ as _);
+        assert_eq!(features.dynamic_rendering.dynamicRendering, vk::VK_TRUE);
+
+        let features2 = features.chain(VULKAN_1_1, &extensions);
+        assert_eq!(features2.pNext, &mut features.dynamic_rendering as *mut _ as _);
+
+        features.chain(VULKAN_1_2, &[]);
+        assert!(features.vulkan12.pNext.is_null());
+
+        features.chain(VULKAN_1_3, &extensions);
+        assert_eq!(features.vulkan12.pNext, &mut features.vulkan13 as *mut _ as _);
+        assert!(features.vulkan13.pNext.is_null());
+    }
+}
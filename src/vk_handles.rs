@@ -0,0 +1,225 @@
+use glfw::ffi::*;
+use vulkan::vulkan as vk;
+use std::ptr::{null, null_mut};
+
+// Owned wrappers around raw handles that destroy themselves on drop. A default constructed
+// wrapper holds a null handle and does nothing when dropped, so a partially initialized
+// application only destroys what it actually created. Wrappers do not track what they depend on:
+// owners have to drop them before the device/instance they were created from.
+
+
+macro_rules! device_handle {
+    ($(#[$attr:meta])* $name:ident, $raw:ty, $destroy:path) => {
+        $(#[$attr])*
+        pub struct $name {
+            device: vk::VkDevice,
+            handle: $raw
+        }
+
+        impl $name {
+            /// Takes ownership of `handle`, which has to have been created from `device`.
+            pub fn new(device: vk::VkDevice, handle: $raw) -> Self {
+                Self {device, handle}
+            }
+
+
+            pub fn raw(&self) -> $raw {
+                self.handle
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self {device: null_mut(), handle: null_mut()}
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                if !self.handle.is_null() {
+                    unsafe{$destroy(self.device, self.handle, null())};
+                }
+            }
+        }
+    };
+}
+
+device_handle!(Swapchain, vk::VkSwapchainKHR, vk::vkDestroySwapchainKHR);
+device_handle!(Image, vk::VkImage, vk::vkDestroyImage);
+device_handle!(ImageView, vk::VkImageView, vk::vkDestroyImageView);
+device_handle!(Buffer, vk::VkBuffer, vk::vkDestroyBuffer);
+device_handle!(DeviceMemory, vk::VkDeviceMemory, vk::vkFreeMemory);
+device_handle!(RenderPass, vk::VkRenderPass, vk::vkDestroyRenderPass);
+device_handle!(PipelineLayout, vk::VkPipelineLayout, vk::vkDestroyPipelineLayout);
+device_handle!(Pipeline, vk::VkPipeline, vk::vkDestroyPipeline);
+device_handle!(PipelineCache, vk::VkPipelineCache, vk::vkDestroyPipelineCache);
+device_handle!(Framebuffer, vk::VkFramebuffer, vk::vkDestroyFramebuffer);
+device_handle!(
+    /// Command buffers allocated from the pool are freed along with it.
+    CommandPool, vk::VkCommandPool, vk::vkDestroyCommandPool
+);
+device_handle!(Semaphore, vk::VkSemaphore, vk::vkDestroySemaphore);
+device_handle!(Fence, vk::VkFence, vk::vkDestroyFence);
+device_handle!(ShaderModule, vk::VkShaderModule, vk::vkDestroyShaderModule);
+device_handle!(Sampler, vk::VkSampler, vk::vkDestroySampler);
+device_handle!(DescriptorSetLayout, vk::VkDescriptorSetLayout, vk::vkDestroyDescriptorSetLayout);
+device_handle!(
+    /// Descriptor sets allocated from the pool are freed along with it.
+    DescriptorPool, vk::VkDescriptorPool, vk::vkDestroyDescriptorPool
+);
+
+
+pub struct Instance {
+    handle: vk::VkInstance
+}
+
+impl Instance {
+    pub fn new(handle: vk::VkInstance) -> Self {
+        Self {handle}
+    }
+
+
+    pub fn raw(&self) -> vk::VkInstance {
+        self.handle
+    }
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {handle: null_mut()}
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            unsafe{vk::vkDestroyInstance(self.handle, null())};
+        }
+    }
+}
+
+
+pub struct Device {
+    handle: vk::VkDevice
+}
+
+impl Device {
+    pub fn new(handle: vk::VkDevice) -> Self {
+        Self {handle}
+    }
+
+
+    pub fn raw(&self) -> vk::VkDevice {
+        self.handle
+    }
+}
+
+impl Default for Device {
+    fn default() -> Self {
+        Self {handle: null_mut()}
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            unsafe{vk::vkDestroyDevice(self.handle, null())};
+        }
+    }
+}
+
+
+pub struct Surface {
+    instance: vk::VkInstance,
+    handle: vk::VkSurfaceKHR
+}
+
+impl Surface {
+    pub fn new(instance: vk::VkInstance, handle: vk::VkSurfaceKHR) -> Self {
+        Self {instance, handle}
+    }
+
+
+    pub fn raw(&self) -> vk::VkSurfaceKHR {
+        self.handle
+    }
+}
+
+impl Default for Surface {
+    fn default() -> Self {
+        Self {instance: null_mut(), handle: null_mut()}
+    }
+}
+
+impl Drop for Surface {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            unsafe{vk::vkDestroySurfaceKHR(self.instance, self.handle, null())};
+        }
+    }
+}
+
+
+pub struct DebugMessenger {
+    instance: vk::VkInstance,
+    handle: vk::VkDebugUtilsMessengerEXT
+}
+
+impl DebugMessenger {
+    pub fn new(instance: vk::VkInstance, handle: vk::VkDebugUtilsMessengerEXT) -> Self {
+        Self {instance, handle}
+    }
+}
+
+impl Default for DebugMessenger {
+    fn default() -> Self {
+        Self {instance: null_mut(), handle: null_mut()}
+    }
+}
+
+impl Drop for DebugMessenger {
+    fn drop(&mut self) {
+        if self.handle.is_null() {return}
+
+        let option_fn_ptr = unsafe{std::mem::transmute::<_, vk::PFN_vkDestroyDebugUtilsMessengerEXT>(
+            vk::vkGetInstanceProcAddr(self.instance, "vkDestroyDebugUtilsMessengerEXT\0".as_ptr() as _))};
+        if let Some(fn_ptr) = option_fn_ptr {
+            unsafe{fn_ptr(self.instance, self.handle, null())}
+        }
+    }
+}
+
+
+/// A GLFW window. GLFW itself is terminated along with it, so there can only be one.
+pub struct Window {
+    handle: *mut GLFWwindow
+}
+
+impl Window {
+    /// Takes ownership of `handle` and of the initialized GLFW library.
+    pub fn new(handle: *mut GLFWwindow) -> Self {
+        Self {handle}
+    }
+
+
+    pub fn raw(&self) -> *mut GLFWwindow {
+        self.handle
+    }
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self {handle: null_mut()}
+    }
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        if !self.handle.is_null() {
+            unsafe {
+                glfwDestroyWindow(self.handle);
+                glfwTerminate();
+            }
+        }
+    }
+}
\ No newline at end of file
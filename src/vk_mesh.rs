@@ -0,0 +1,238 @@
+use vulkan::vulkan as vk;
+use crate::vk_error::{unsupported, Result, Stage};
+use std::mem::{offset_of, size_of};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vertex {
+    pub pos: [f32; 2],
+    pub color: [f32; 3],
+    pub tex_coord: [f32; 2]
+}
+
+impl Vertex {
+    pub const fn new(pos: [f32; 2], color: [f32; 3], tex_coord: [f32; 2]) -> Self {
+        Self {pos, color, tex_coord}
+    }
+
+
+    pub fn binding_description() -> vk::VkVertexInputBindingDescription {
+        let mut binding_description = vk::VkVertexInputBindingDescription::default();
+        binding_description.binding = 0;
+        binding_description.stride = size_of::<Vertex>() as u32;
+        binding_description.inputRate = vk::VK_VERTEX_INPUT_RATE_VERTEX;
+
+        binding_description
+    }
+
+
+    /// One attribute per field, with locations matching the inputs of `shaders/shader.vert`. The vertex input of the
+    /// pipeline is reflected from the vertex shader and has to match these.
+    pub fn attribute_descriptions() -> [vk::VkVertexInputAttributeDescription; 3] {
+        let mut attribute_descriptions = [vk::VkVertexInputAttributeDescription::default(); 3];
+
+        attribute_descriptions[0].binding = 0;
+        attribute_descriptions[0].location = 0;
+        attribute_descriptions[0].format = vk::VK_FORMAT_R32G32_SFLOAT;
+        attribute_descriptions[0].offset = offset_of!(Vertex, pos) as u32;
+
+        attribute_descriptions[1].binding = 0;
+        attribute_descriptions[1].location = 1;
+        attribute_descriptions[1].format = vk::VK_FORMAT_R32G32B32_SFLOAT;
+        attribute_descriptions[1].offset = offset_of!(Vertex, color) as u32;
+
+        attribute_descriptions[2].binding = 0;
+        attribute_descriptions[2].location = 2;
+        attribute_descriptions[2].format = vk::VK_FORMAT_R32G32_SFLOAT;
+        attribute_descriptions[2].offset = offset_of!(Vertex, tex_coord) as u32;
+
+        attribute_descriptions
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>)
+}
+
+impl Indices {
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len()
+        }
+    }
+
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+
+    /// The largest index, `None` if there are none.
+    pub fn max(&self) -> Option<u32> {
+        match self {
+            Indices::U16(indices) => indices.iter().max().map(|&index| index as u32),
+            Indices::U32(indices) => indices.iter().max().copied()
+        }
+    }
+
+
+    pub fn index_type(&self) -> vk::VkIndexType {
+        match self {
+            Indices::U16(_) => vk::VK_INDEX_TYPE_UINT16,
+            Indices::U32(_) => vk::VK_INDEX_TYPE_UINT32
+        }
+    }
+
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Indices::U16(indices) => as_bytes(indices),
+            Indices::U32(indices) => as_bytes(indices)
+        }
+    }
+}
+
+impl From<Vec<u16>> for Indices {
+    fn from(indices: Vec<u16>) -> Self {
+        Indices::U16(indices)
+    }
+}
+
+impl From<Vec<u32>> for Indices {
+    fn from(indices: Vec<u32>) -> Self {
+        Indices::U32(indices)
+    }
+}
+
+
+/// Geometry uploaded to the gpu when the application initializes.
+/// Drawn with `vkCmdDrawIndexed` when it has indices, and with `vkCmdDraw` otherwise.
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    vertices: Vec<Vertex>,
+    indices: Option<Indices>
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Vertex>) -> Self {
+        Self {vertices, indices: None}
+    }
+
+
+    /// Fails if there are no indices or one of them is out of range of `vertices`, which a draw would read past the
+    /// end of the vertex buffer with.
+    pub fn indexed(vertices: Vec<Vertex>, indices: impl Into<Indices>) -> Result<Self> {
+        let indices = indices.into();
+        if indices.is_empty() {
+            return unsupported(Stage::Mesh, "the mesh has an empty index list");
+        }
+        match indices.max() {
+            Some(max) if max as usize >= vertices.len() => unsupported(Stage::Mesh, &format!(
+                "the mesh has index {} but only {} vertices", max, vertices.len())),
+            _ => Ok(Self {vertices, indices: Some(indices)})
+        }
+    }
+
+
+    pub fn triangle() -> Self {
+        Self::new(vec![
+            Vertex::new([0.0, -0.5], [1.0, 0.0, 0.0], [0.5, 0.0]),
+            Vertex::new([0.5, 0.5], [0.0, 1.0, 0.0], [1.0, 1.0]),
+            Vertex::new([-0.5, 0.5], [0.0, 0.0, 1.0], [0.0, 1.0])
+        ])
+    }
+
+
+    /// Two triangles sharing the diagonal of a quad.
+    pub fn quad() -> Self {
+        Self::indexed(
+            vec![
+                Vertex::new([-0.5, -0.5], [1.0, 0.0, 0.0], [0.0, 0.0]),
+                Vertex::new([0.5, -0.5], [0.0, 1.0, 0.0], [1.0, 0.0]),
+                Vertex::new([0.5, 0.5], [0.0, 0.0, 1.0], [1.0, 1.0]),
+                Vertex::new([-0.5, 0.5], [1.0, 1.0, 1.0], [0.0, 1.0])
+            ],
+            vec![0u16, 1, 2, 2, 3, 0]
+        )
+            .expect("the indices of the quad are in range")
+    }
+
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+
+    pub fn indices(&self) -> Option<&Indices> {
+        self.indices.as_ref()
+    }
+
+
+    pub fn vertex_bytes(&self) -> &[u8] {
+        as_bytes(&self.vertices)
+    }
+
+
+    /// Number of vertices or indices consumed by a draw of the whole mesh.
+    pub fn draw_count(&self) -> u32 {
+        match &self.indices {
+            Some(indices) => indices.len() as u32,
+            None => self.vertices.len() as u32
+        }
+    }
+}
+
+impl Default for Mesh {
+    fn default() -> Self {
+        Self::triangle()
+    }
+}
+
+
+/// Views plain-old-data as raw bytes for uploading.
+fn as_bytes<T: Copy>(data: &[T]) -> &[u8] {
+    unsafe{std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))}
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertices(count: usize) -> Vec<Vertex> {
+        vec![Vertex::default(); count]
+    }
+
+
+    #[test]
+    fn indexed_accepts_indices_in_range() {
+        let mesh = Mesh::indexed(vertices(3), vec![0u32, 1, 2]).unwrap();
+        assert_eq!(mesh.draw_count(), 3);
+        assert_eq!(mesh.indices().map(Indices::index_type), Some(vk::VK_INDEX_TYPE_UINT32));
+    }
+
+
+    #[test]
+    fn indexed_rejects_indices_out_of_range() {
+        assert!(matches!(Mesh::indexed(vertices(3), vec![0u16, 1, 3]), Err(error) if error.stage() == Stage::Mesh));
+        assert!(Mesh::indexed(vertices(0), vec![0u32]).is_err());
+    }
+
+
+    #[test]
+    fn indexed_rejects_empty_indices() {
+        assert!(matches!(Mesh::indexed(vertices(3), Vec::<u16>::new()), Err(error) if error.stage() == Stage::Mesh));
+    }
+
+
+    #[test]
+    fn quad_draws_two_triangles() {
+        let quad = Mesh::quad();
+        assert_eq!(quad.vertices().len(), 4);
+        assert_eq!(quad.draw_count(), 6);
+    }
+}
@@ -0,0 +1,164 @@
+use vulkan::vulkan as vk;
+use std::path::{Path, PathBuf};
+
+// header length, header version, vendor id and device id, followed by the pipeline cache UUID
+const HEADER_SIZE: usize = 16 + vk::VK_UUID_SIZE as usize;
+
+const CACHE_FILE_NAME: &str = "pipeline_cache.bin";
+
+/// Where the cache is kept unless another path is set: a directory of the application in the user's cache
+/// directory, or in the temporary directory if there is none.
+pub fn default_path() -> PathBuf {
+    let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .unwrap_or_else(std::env::temp_dir);
+
+    cache_dir.join("hello-triangle").join(CACHE_FILE_NAME)
+}
+
+
+/// Reads cache data to seed the pipeline cache of the device with `properties`. A missing file is the normal first
+/// run and returns `None` quietly. Data written by another device or driver is ignored with a message, as are
+/// unreadable files, so a bad cache only costs the time it would have saved.
+pub fn load(path: &Path, properties: &vk::VkPhysicalDeviceProperties) -> Option<Vec<u8>> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(error) => {
+            eprintln!("pipeline cache: ignoring {}, {}", path.display(), error);
+            return None;
+        }
+    };
+
+    match header_mismatch(&data, properties) {
+        None => Some(data),
+        Some(reason) => {
+            eprintln!("pipeline cache: ignoring {}, {}", path.display(), reason);
+            None
+        }
+    }
+}
+
+
+/// Why `data` cannot seed a cache on the device with `properties`, or `None` if its header matches the device.
+pub fn header_mismatch(data: &[u8], properties: &vk::VkPhysicalDeviceProperties) -> Option<String> {
+    if data.len() < HEADER_SIZE {
+        return Some(format!("{} bytes are too short for a header", data.len()));
+    }
+    // the header is written in the byte order of the host
+    let word = |index: usize| u32::from_ne_bytes(data[index * 4..index * 4 + 4].try_into().unwrap());
+
+    let (header_size, header_version, vendor_id, device_id) = (word(0), word(1), word(2), word(3));
+    if (header_size as usize) < HEADER_SIZE || header_size as usize > data.len() {
+        return Some(format!("the header size of {} bytes is invalid", header_size));
+    }
+    if header_version != vk::VK_PIPELINE_CACHE_HEADER_VERSION_ONE as u32 {
+        return Some(format!("header version {} is unknown", header_version));
+    }
+    if vendor_id != properties.vendorID || device_id != properties.deviceID {
+        return Some(format!(
+            "it was written for device {:04x}:{:04x}, not {:04x}:{:04x}",
+            vendor_id, device_id, properties.vendorID, properties.deviceID
+        ));
+    }
+    if data[16..HEADER_SIZE] != properties.pipelineCacheUUID[..] {
+        return Some("it was written by a different driver version".to_string());
+    }
+
+    None
+}
+
+
+/// Replaces the file at `path` with `data`, creating its directory if needed. The data is written to a temporary
+/// file first, so an interrupted write never leaves a truncated cache behind.
+pub fn save(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    // unique per process and call, so concurrent runs never write to the same temporary file
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let mut temporary_name = path.file_name().unwrap_or(CACHE_FILE_NAME.as_ref()).to_os_string();
+    temporary_name.push(format!(".{}.{}.tmp", std::process::id(), nanos));
+    let temporary = path.with_file_name(temporary_name);
+    std::fs::write(&temporary, data)?;
+    std::fs::rename(&temporary, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temporary);
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> vk::VkPhysicalDeviceProperties {
+        let mut properties = vk::VkPhysicalDeviceProperties::default();
+        properties.vendorID = 0x10de;
+        properties.deviceID = 0x2204;
+        properties.pipelineCacheUUID = [7; vk::VK_UUID_SIZE as usize];
+        properties
+    }
+
+
+    // a header written by the device with `properties`, followed by some cache data
+    fn cache_data(properties: &vk::VkPhysicalDeviceProperties) -> Vec<u8> {
+        let header_version = vk::VK_PIPELINE_CACHE_HEADER_VERSION_ONE as u32;
+        let header = [HEADER_SIZE as u32, header_version, properties.vendorID, properties.deviceID];
+        let mut data: Vec<u8> = header.iter().flat_map(|word| word.to_ne_bytes()).collect();
+        data.extend(properties.pipelineCacheUUID);
+        data.extend([1, 2, 3, 4]);
+        data
+    }
+
+
+    #[test]
+    fn data_of_the_same_device_and_driver_matches() {
+        assert_eq!(header_mismatch(&cache_data(&properties()), &properties()), None);
+    }
+
+
+    #[test]
+    fn truncated_headers_do_not_match() {
+        let data = cache_data(&properties());
+        assert!(header_mismatch(&data[..HEADER_SIZE - 1], &properties()).is_some());
+        assert!(header_mismatch(&[], &properties()).is_some());
+    }
+
+
+    #[test]
+    fn data_of_another_vendor_or_device_does_not_match() {
+        let mut other = properties();
+        other.vendorID = 0x1002;
+        assert!(header_mismatch(&cache_data(&other), &properties()).is_some());
+
+        let mut other = properties();
+        other.deviceID += 1;
+        assert!(header_mismatch(&cache_data(&other), &properties()).is_some());
+    }
+
+
+    #[test]
+    fn data_of_another_driver_does_not_match() {
+        let mut other = properties();
+        other.pipelineCacheUUID[0] = 8;
+        assert!(header_mismatch(&cache_data(&other), &properties()).is_some());
+    }
+
+
+    #[test]
+    fn saved_data_is_loaded_back() {
+        let path = std::env::temp_dir()
+            .join(format!("hello-triangle-test-{}", std::process::id()))
+            .join(CACHE_FILE_NAME);
+        let data = cache_data(&properties());
+        save(&path, &data).unwrap();
+        let loaded = load(&path, &properties());
+        let entries = std::fs::read_dir(path.parent().unwrap()).unwrap().count();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(loaded, Some(data));
+        assert_eq!(entries, 1, "the temporary file was left behind");
+    }
+}
@@ -0,0 +1,184 @@
+use vulkan::vulkan as vk;
+use crate::vk_error::{unsupported, Result, Stage};
+use crate::vk_uniform::{Mat4, IDENTITY};
+use std::mem::size_of;
+
+/// A push constant range of a pipeline layout, sized after the `#[repr(C)]` struct pushed into it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PushConstantRange {
+    pub stages: vk::VkShaderStageFlags,
+    pub offset: u32,
+    pub size: u32
+}
+
+impl PushConstantRange {
+    /// A range holding one `T` at `offset`, readable from `stages`.
+    pub fn of<T: Copy>(stages: vk::VkShaderStageFlags, offset: u32) -> Self {
+        Self {stages, offset, size: size_of::<T>() as u32}
+    }
+
+
+    pub fn end(&self) -> u32 {
+        self.offset + self.size
+    }
+
+
+    /// Whether a push of `size` bytes at `offset` to `stages` lies inside this range.
+    pub fn contains(&self, stages: vk::VkShaderStageFlags, offset: u32, size: u32) -> bool {
+        stages & self.stages == stages && offset >= self.offset && offset + size <= self.end()
+    }
+
+
+    /// Whether this range shares a byte with the `size` bytes at `offset`.
+    pub fn overlaps(&self, offset: u32, size: u32) -> bool {
+        offset < self.end() && self.offset < offset + size
+    }
+
+
+    pub fn to_vk(&self) -> vk::VkPushConstantRange {
+        let mut range = vk::VkPushConstantRange::default();
+        range.stageFlags = self.stages;
+        range.offset = self.offset;
+        range.size = self.size;
+
+        range
+    }
+}
+
+
+/// Per-draw parameters, mirroring the push constant block in `shaders/draw_push_constants.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawPushConstants {
+    /// Applied before the model matrix of the uniform buffer.
+    pub transform: Mat4,
+    /// Multiplied with the vertex color.
+    pub tint: [f32; 4]
+}
+
+impl DrawPushConstants {
+    pub fn range() -> PushConstantRange {
+        PushConstantRange::of::<Self>((vk::VK_SHADER_STAGE_VERTEX_BIT | vk::VK_SHADER_STAGE_FRAGMENT_BIT) as _, 0)
+    }
+}
+
+impl Default for DrawPushConstants {
+    fn default() -> Self {
+        Self {transform: IDENTITY, tint: [1.0; 4]}
+    }
+}
+
+
+/// A function run before every draw, see `HelloTriangleApplication::set_draw_hook`.
+pub type DrawHook = Box<dyn Fn(&DrawContext) -> Result<()>>;
+
+
+/// The command buffer of a draw that is being recorded, inside the render pass and with the pipeline, vertex buffer
+/// and descriptor sets bound.
+pub struct DrawContext<'a> {
+    pub command_buffer: vk::VkCommandBuffer,
+    pub layout: vk::VkPipelineLayout,
+    // the ranges `layout` was created with
+    ranges: &'a [PushConstantRange]
+}
+
+impl<'a> DrawContext<'a> {
+    pub fn new(command_buffer: vk::VkCommandBuffer, layout: vk::VkPipelineLayout, ranges: &'a [PushConstantRange]) -> Self {
+        Self {command_buffer, layout, ranges}
+    }
+
+
+    /// Whether a push of `size` bytes at `offset` to `stages` is allowed by the ranges of the layout: it has to be
+    /// 4 byte aligned, fit into one range that is visible to every stage it targets, and target every stage of the
+    /// ranges it overlaps.
+    pub fn covers(&self, stages: vk::VkShaderStageFlags, offset: u32, size: u32) -> bool {
+        size > 0 && offset % 4 == 0 && size % 4 == 0
+            && self.ranges.iter().any(|range| range.contains(stages, offset, size))
+            && self.ranges.iter()
+                .filter(|range| range.overlaps(offset, size))
+                .all(|range| stages & range.stages == range.stages)
+    }
+
+
+    /// Records a push of `value` at `offset`. `T` has to be plain `#[repr(C)]` data laid out like the shader's push
+    /// constant block. Fails without recording anything if the ranges of the layout do not cover the push.
+    pub fn push_constants<T: Copy>(&self, stages: vk::VkShaderStageFlags, offset: u32, value: &T) -> Result<()> {
+        let size = size_of::<T>() as u32;
+        if !self.covers(stages, offset, size) {
+            return unsupported(Stage::Draw, &format!(
+                "a push of {} bytes at offset {} to stages {:#x} is not covered by the push constant ranges of the pipeline",
+                size, offset, stages));
+        }
+
+        cmd_push_constants(self.command_buffer, self.layout, stages, offset, value);
+        Ok(())
+    }
+}
+
+
+/// Records a push of `value` at `offset` without checking it, see `DrawContext::push_constants`.
+fn cmd_push_constants<T: Copy>(
+    command_buffer: vk::VkCommandBuffer,
+    layout: vk::VkPipelineLayout,
+    stages: vk::VkShaderStageFlags,
+    offset: u32,
+    value: &T
+) {
+    unsafe {
+        vk::vkCmdPushConstants(
+            command_buffer,
+            layout,
+            stages,
+            offset,
+            size_of::<T>() as u32,
+            value as *const T as *const std::ffi::c_void
+        )
+    };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERTEX: vk::VkShaderStageFlags = vk::VK_SHADER_STAGE_VERTEX_BIT as _;
+    const FRAGMENT: vk::VkShaderStageFlags = vk::VK_SHADER_STAGE_FRAGMENT_BIT as _;
+
+
+    fn covers(ranges: &[PushConstantRange], stages: vk::VkShaderStageFlags, offset: u32, size: u32) -> bool {
+        DrawContext::new(std::ptr::null_mut(), std::ptr::null_mut(), ranges).covers(stages, offset, size)
+    }
+
+
+    #[test]
+    fn pushes_have_to_lie_inside_one_range() {
+        let ranges = [PushConstantRange {stages: VERTEX | FRAGMENT, offset: 0, size: 80}];
+        assert!(covers(&ranges, VERTEX | FRAGMENT, 0, 80));
+        assert!(covers(&ranges, VERTEX | FRAGMENT, 64, 16));
+        assert!(!covers(&ranges, VERTEX | FRAGMENT, 64, 32));
+        assert!(!covers(&ranges, vk::VK_SHADER_STAGE_COMPUTE_BIT as _, 0, 16));
+    }
+
+
+    #[test]
+    fn pushes_have_to_target_every_stage_of_the_ranges_they_overlap() {
+        assert!(!covers(&[DrawPushConstants::range()], VERTEX, 0, 64));
+
+        let ranges = [
+            PushConstantRange {stages: VERTEX, offset: 0, size: 64},
+            PushConstantRange {stages: FRAGMENT, offset: 64, size: 16}
+        ];
+        assert!(covers(&ranges, VERTEX, 0, 64));
+        assert!(covers(&ranges, FRAGMENT, 64, 16));
+        assert!(!covers(&ranges, VERTEX, 48, 32));
+    }
+
+
+    #[test]
+    fn pushes_have_to_be_aligned() {
+        let ranges = [DrawPushConstants::range()];
+        assert!(!covers(&ranges, VERTEX | FRAGMENT, 2, 4));
+        assert!(!covers(&ranges, VERTEX | FRAGMENT, 0, 6));
+        assert!(!covers(&ranges, VERTEX | FRAGMENT, 0, 0));
+    }
+}
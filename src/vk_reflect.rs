@@ -0,0 +1,862 @@
+use vulkan::vulkan as vk;
+use crate::vk_error::{AppError, Result, Stage};
+use crate::vk_push::PushConstantRange;
+use std::collections::{HashMap, HashSet};
+
+// Reads what a pipeline needs to know about its shaders straight out of their SPIR-V: the stage interface,
+// descriptor bindings and push constant blocks. Only the instructions that carry this information are decoded,
+// everything else is skipped by its word count.
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+// the newest SPIR-V version the parser knows, as minor version of 1
+const MAX_SPIRV_MINOR_VERSION: u32 = 6;
+
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_SPEC_CONSTANT: u32 = 50;
+const OP_SPEC_CONSTANT_OP: u32 = 52;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_OUTPUT: u32 = 3;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+
+/// A stage input or output with a location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InterfaceVariable {
+    pub location: u32,
+    /// Matrices take one location per column, all with the format of a column.
+    pub locations: u32,
+    pub format: vk::VkFormat,
+    /// Byte size of the whole variable, as a vertex attribute.
+    pub size: u32,
+    pub name: String
+}
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::VkDescriptorType,
+    pub count: u32,
+    pub stages: vk::VkShaderStageFlags,
+    pub name: String
+}
+
+
+/// What one shader module exposes through its `main` entry point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShaderReflection {
+    pub stage: vk::VkShaderStageFlags,
+    pub inputs: Vec<InterfaceVariable>,
+    pub outputs: Vec<InterfaceVariable>,
+    pub descriptor_bindings: Vec<DescriptorBinding>,
+    /// Byte range covered by the push constant block, if there is one.
+    pub push_constants: Option<PushConstantRange>
+}
+
+
+/// The layout of a vertex + fragment pipeline, derived from both stages' reflection.
+#[derive(Clone, Debug, Default)]
+pub struct PipelineInterface {
+    /// Attributes of a single interleaved vertex buffer at binding 0, packed in location order.
+    pub vertex_attributes: Vec<vk::VkVertexInputAttributeDescription>,
+    pub vertex_stride: u32,
+    /// Bindings of both stages, sorted by set and binding, with the stages merged.
+    pub descriptor_bindings: Vec<DescriptorBinding>,
+    pub push_constant_ranges: Vec<PushConstantRange>
+}
+
+impl PipelineInterface {
+    /// Combines the reflection of both stages, checking that every fragment input is written by the vertex stage
+    /// with the same format and that bindings shared by both stages agree. `name` is used in errors.
+    pub fn new(vertex: &ShaderReflection, fragment: &ShaderReflection, name: &str) -> Result<Self> {
+        for input in &fragment.inputs {
+            match vertex.outputs.iter().find(|output| output.location == input.location) {
+                Some(output) if output.format == input.format && output.locations == input.locations => (),
+                Some(output) => return invalid(name, &format!(
+                    "fragment input {} and vertex output {} at location {} have different types",
+                    input.name, output.name, input.location)),
+                None => return invalid(name, &format!(
+                    "fragment input {} at location {} is not written by the vertex stage", input.name, input.location))
+            }
+        }
+
+        let mut inputs = vertex.inputs.clone();
+        inputs.sort_by_key(|input| input.location);
+        let mut vertex_attributes = Vec::new();
+        let mut vertex_stride = 0;
+        for input in &inputs {
+            let column_size = input.size / input.locations;
+            for column in 0..input.locations {
+                let mut attribute = vk::VkVertexInputAttributeDescription::default();
+                attribute.binding = 0;
+                attribute.location = input.location + column;
+                attribute.format = input.format;
+                attribute.offset = vertex_stride;
+                vertex_attributes.push(attribute);
+                vertex_stride += column_size;
+            }
+        }
+
+        let mut descriptor_bindings: Vec<DescriptorBinding> = Vec::new();
+        for binding in vertex.descriptor_bindings.iter().chain(&fragment.descriptor_bindings) {
+            match descriptor_bindings.iter_mut().find(|merged| merged.set == binding.set && merged.binding == binding.binding) {
+                Some(merged) if merged.descriptor_type == binding.descriptor_type && merged.count == binding.count =>
+                    merged.stages |= binding.stages,
+                Some(merged) => return invalid(name, &format!(
+                    "{} and {} are both bound to set {} binding {} with different types",
+                    merged.name, binding.name, binding.set, binding.binding)),
+                None => descriptor_bindings.push(binding.clone())
+            }
+        }
+        descriptor_bindings.sort_by_key(|binding| (binding.set, binding.binding));
+
+        // a stage may only appear in one range, so both blocks share a single range covering them
+        let blocks: Vec<PushConstantRange> = [vertex.push_constants, fragment.push_constants].into_iter().flatten().collect();
+        let push_constant_ranges = match blocks.as_slice() {
+            [] => Vec::new(),
+            [first, rest @ ..] => {
+                let offset = rest.iter().map(|block| block.offset).fold(first.offset, u32::min);
+                let end = rest.iter().map(PushConstantRange::end).fold(first.end(), u32::max);
+                let stages = rest.iter().fold(first.stages, |stages, block| stages | block.stages);
+                vec![PushConstantRange {stages, offset, size: end - offset}]
+            }
+        };
+
+        Ok(Self {vertex_attributes, vertex_stride, descriptor_bindings, push_constant_ranges})
+    }
+
+
+    /// Whether vertices laid out as `binding` and `attributes` describe can be fed to the vertex stage as is.
+    pub fn matches_vertex_input(
+        &self,
+        binding: &vk::VkVertexInputBindingDescription,
+        attributes: &[vk::VkVertexInputAttributeDescription]
+    ) -> bool {
+        self.vertex_stride == binding.stride
+            && self.vertex_attributes.len() == attributes.len()
+            && self.vertex_attributes.iter().zip(attributes).all(|(reflected, attribute)| {
+                reflected.location == attribute.location
+                    && reflected.format == attribute.format
+                    && reflected.offset == attribute.offset
+            })
+    }
+
+
+    pub fn vertex_binding_description(&self) -> vk::VkVertexInputBindingDescription {
+        let mut binding_description = vk::VkVertexInputBindingDescription::default();
+        binding_description.binding = 0;
+        binding_description.stride = self.vertex_stride;
+        binding_description.inputRate = vk::VK_VERTEX_INPUT_RATE_VERTEX;
+
+        binding_description
+    }
+}
+
+
+/// Checks that `code` is a SPIR-V module that can be handed to `vkCreateShaderModule` and returns its words in host
+/// byte order, which also gives them the alignment Vulkan requires. The header, the length of every instruction and
+/// the entry points are checked, and a `main` entry point has to exist. `name` identifies the module in errors.
+pub fn validate(code: &[u8], name: &str) -> Result<Vec<u32>> {
+    if code.len() % 4 != 0 {
+        return malformed(name, &format!("its length of {} bytes is not a multiple of 4", code.len()));
+    }
+    if code.len() < HEADER_WORDS * 4 {
+        return malformed(name, "it is too short to hold a header");
+    }
+
+    let mut words: Vec<u32> = code.chunks_exact(4).map(|word| u32::from_le_bytes(word.try_into().unwrap())).collect();
+    if words[0] == SPIRV_MAGIC.swap_bytes() {
+        words.iter_mut().for_each(|word| *word = word.swap_bytes());
+    } else if words[0] != SPIRV_MAGIC {
+        return malformed(name, &format!("the magic number is {:#010x} instead of {:#010x}", words[0], SPIRV_MAGIC));
+    }
+
+    let version = words[1];
+    let (major, minor) = (version >> 16 & 0xff, version >> 8 & 0xff);
+    if version & 0xff00_00ff != 0 || major != 1 || minor > MAX_SPIRV_MINOR_VERSION {
+        return malformed(name, &format!("version {}.{} ({:#010x}) is not supported", major, minor, version));
+    }
+    let bound = words[3];
+    if bound == 0 {
+        return malformed(name, "the id bound is 0");
+    }
+    if words[4] != 0 {
+        return malformed(name, "the reserved schema word is not 0");
+    }
+
+    let module = Module::parse(&words, name)?;
+    if module.entry_points.is_empty() {
+        return malformed(name, "it has no entry point");
+    }
+    for entry_point in &module.entry_points {
+        if entry_point.id >= bound || entry_point.interface.iter().any(|&id| id >= bound) {
+            return malformed(name, &format!("entry point {} uses ids beyond the bound of {}", entry_point.name, bound));
+        }
+        stage_of(entry_point, name)?;
+    }
+    module.main_entry_point()?;
+
+    Ok(words)
+}
+
+
+/// Reflects the `main` entry point of a module checked by `validate`. `name` identifies the module in errors.
+pub fn reflect(words: &[u32], name: &str) -> Result<ShaderReflection> {
+    let module = Module::parse(words, name)?;
+    module.reflect()
+}
+
+
+#[derive(Clone, Debug)]
+enum Type {
+    Bool,
+    Int {width: u32, signed: bool},
+    Float {width: u32},
+    Vector {component: u32, count: u32},
+    Matrix {column: u32, count: u32},
+    Image {dim: u32, sampled: u32},
+    Sampler,
+    SampledImage,
+    Array {element: u32, length: u32},
+    RuntimeArray,
+    Struct {members: Vec<u32>},
+    Pointer {pointee: u32}
+}
+
+
+#[derive(Default)]
+struct Decorations {
+    location: Option<u32>,
+    binding: Option<u32>,
+    set: Option<u32>,
+    array_stride: Option<u32>,
+    block: bool,
+    buffer_block: bool
+}
+
+
+#[derive(Default)]
+struct MemberDecorations {
+    offset: Option<u32>,
+    matrix_stride: Option<u32>
+}
+
+
+struct EntryPoint {
+    execution_model: u32,
+    id: u32,
+    name: String,
+    interface: Vec<u32>
+}
+
+
+struct Variable {
+    id: u32,
+    pointer_type: u32,
+    storage_class: u32
+}
+
+
+#[derive(Default)]
+struct Module<'a> {
+    name: &'a str,
+    names: HashMap<u32, String>,
+    entry_points: Vec<EntryPoint>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    // ids of OpSpecConstantOp results, whose value is only known once the pipeline is specialized
+    spec_constant_ops: HashSet<u32>,
+    variables: Vec<Variable>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), MemberDecorations>
+}
+
+impl<'a> Module<'a> {
+    /// Decodes the instructions after the header of `words`, which are in host byte order.
+    fn parse(words: &[u32], name: &'a str) -> Result<Self> {
+        let mut module = Module {name, ..Default::default()};
+        let mut position = HEADER_WORDS;
+        while position < words.len() {
+            let word_count = (words[position] >> 16) as usize;
+            let opcode = words[position] & 0xffff;
+            if word_count == 0 || position + word_count > words.len() {
+                return malformed(name, &format!("the instruction at word {} has an invalid length", position));
+            }
+            module.instruction(opcode, &words[position + 1..position + word_count])?;
+            position += word_count;
+        }
+
+        Ok(module)
+    }
+
+
+    /// Records one instruction, `operands` being everything after its first word.
+    fn instruction(&mut self, opcode: u32, operands: &[u32]) -> Result<()> {
+        let required = match opcode {
+            OP_NAME | OP_TYPE_BOOL | OP_TYPE_SAMPLER | OP_TYPE_SAMPLED_IMAGE | OP_TYPE_RUNTIME_ARRAY | OP_TYPE_STRUCT => 1,
+            OP_ENTRY_POINT | OP_TYPE_FLOAT | OP_DECORATE => 2,
+            OP_TYPE_INT | OP_TYPE_VECTOR | OP_TYPE_MATRIX | OP_TYPE_ARRAY | OP_TYPE_POINTER | OP_CONSTANT
+                | OP_SPEC_CONSTANT | OP_SPEC_CONSTANT_OP | OP_VARIABLE | OP_MEMBER_DECORATE => 3,
+            OP_TYPE_IMAGE => 7,
+            _ => 0
+        };
+        if operands.len() < required {
+            return malformed(self.name, &format!("instruction {} is missing operands", opcode));
+        }
+
+        match opcode {
+            OP_NAME => {
+                self.names.insert(operands[0], literal_string(&operands[1..]).0);
+            }
+            OP_ENTRY_POINT => {
+                let (name, words) = literal_string(&operands[2..]);
+                let interface = operands[2 + words..].to_vec();
+                self.entry_points.push(EntryPoint {execution_model: operands[0], id: operands[1], name, interface});
+            }
+            OP_TYPE_BOOL => {
+                self.types.insert(operands[0], Type::Bool);
+            }
+            OP_TYPE_INT => {
+                self.types.insert(operands[0], Type::Int {width: operands[1], signed: operands[2] != 0});
+            }
+            OP_TYPE_FLOAT => {
+                self.types.insert(operands[0], Type::Float {width: operands[1]});
+            }
+            OP_TYPE_VECTOR => {
+                self.types.insert(operands[0], Type::Vector {component: operands[1], count: operands[2]});
+            }
+            OP_TYPE_MATRIX => {
+                self.types.insert(operands[0], Type::Matrix {column: operands[1], count: operands[2]});
+            }
+            OP_TYPE_IMAGE => {
+                self.types.insert(operands[0], Type::Image {dim: operands[2], sampled: operands[6]});
+            }
+            OP_TYPE_SAMPLER => {
+                self.types.insert(operands[0], Type::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operands[0], Type::SampledImage);
+            }
+            OP_TYPE_ARRAY => {
+                if self.spec_constant_ops.contains(&operands[2]) {
+                    return invalid(self.name, "array lengths computed by OpSpecConstantOp are not supported");
+                }
+                let Some(&length) = self.constants.get(&operands[2]) else {
+                    return malformed(self.name, "an array length is not a constant");
+                };
+                self.types.insert(operands[0], Type::Array {element: operands[1], length});
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                self.types.insert(operands[0], Type::RuntimeArray);
+            }
+            OP_TYPE_STRUCT => {
+                self.types.insert(operands[0], Type::Struct {members: operands[1..].to_vec()});
+            }
+            OP_TYPE_POINTER => {
+                self.types.insert(operands[0], Type::Pointer {pointee: operands[2]});
+            }
+            OP_CONSTANT => {
+                // only the low word matters, constants are read for array lengths
+                self.constants.insert(operands[1], operands[2]);
+            }
+            OP_SPEC_CONSTANT => {
+                // pipelines are created without specialization info, so the default value is the one used
+                self.constants.insert(operands[1], operands[2]);
+            }
+            OP_SPEC_CONSTANT_OP => {
+                self.spec_constant_ops.insert(operands[1]);
+            }
+            OP_VARIABLE => {
+                self.variables.push(Variable {pointer_type: operands[0], id: operands[1], storage_class: operands[2]});
+            }
+            OP_DECORATE => {
+                let value = operands.get(2).copied();
+                let decorations = self.decorations.entry(operands[0]).or_default();
+                match operands[1] {
+                    DECORATION_LOCATION => decorations.location = value,
+                    DECORATION_BINDING => decorations.binding = value,
+                    DECORATION_DESCRIPTOR_SET => decorations.set = value,
+                    DECORATION_ARRAY_STRIDE => decorations.array_stride = value,
+                    DECORATION_BLOCK => decorations.block = true,
+                    DECORATION_BUFFER_BLOCK => decorations.buffer_block = true,
+                    _ => ()
+                }
+            }
+            OP_MEMBER_DECORATE => {
+                let value = operands.get(3).copied();
+                let decorations = self.member_decorations.entry((operands[0], operands[1])).or_default();
+                match operands[2] {
+                    DECORATION_OFFSET => decorations.offset = value,
+                    DECORATION_MATRIX_STRIDE => decorations.matrix_stride = value,
+                    _ => ()
+                }
+            }
+            _ => ()
+        }
+
+        Ok(())
+    }
+
+
+    fn main_entry_point(&self) -> Result<&EntryPoint> {
+        match self.entry_points.iter().find(|entry_point| entry_point.name == "main") {
+            Some(entry_point) => Ok(entry_point),
+            None => malformed(self.name, "there is no entry point named main")
+        }
+    }
+
+
+    fn reflect(&self) -> Result<ShaderReflection> {
+        let entry_point = self.main_entry_point()?;
+        let stage = stage_of(entry_point, self.name)?;
+
+        let mut reflection = ShaderReflection {
+            stage,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            descriptor_bindings: Vec::new(),
+            push_constants: None
+        };
+
+        for variable in &self.variables {
+            let decorations = self.decorations.get(&variable.id);
+            let pointee = match self.types.get(&variable.pointer_type) {
+                Some(Type::Pointer {pointee}) => *pointee,
+                _ => return malformed(self.name, "a variable does not have a pointer type")
+            };
+
+            match variable.storage_class {
+                STORAGE_CLASS_INPUT | STORAGE_CLASS_OUTPUT => {
+                    // built-ins and blocks of them, like gl_PerVertex, have no location
+                    let Some(location) = decorations.and_then(|decorations| decorations.location) else {continue};
+                    if !entry_point.interface.contains(&variable.id) {continue}
+
+                    let interface_variable = self.interface_variable(variable.id, location, pointee)?;
+                    if variable.storage_class == STORAGE_CLASS_INPUT {
+                        reflection.inputs.push(interface_variable);
+                    } else {
+                        reflection.outputs.push(interface_variable);
+                    }
+                }
+                STORAGE_CLASS_UNIFORM | STORAGE_CLASS_UNIFORM_CONSTANT | STORAGE_CLASS_STORAGE_BUFFER => {
+                    let Some(decorations) = decorations else {continue};
+                    let (Some(set), Some(binding)) = (decorations.set, decorations.binding) else {continue};
+
+                    let (descriptor_type, count) = self.descriptor_type(pointee, variable.storage_class)?;
+                    reflection.descriptor_bindings.push(DescriptorBinding {
+                        set,
+                        binding,
+                        descriptor_type,
+                        count,
+                        stages: stage,
+                        name: self.variable_name(variable.id)
+                    });
+                }
+                STORAGE_CLASS_PUSH_CONSTANT => {
+                    let Some(Type::Struct {members}) = self.types.get(&pointee) else {
+                        return malformed(self.name, "the push constant block is not a struct");
+                    };
+                    let mut offset = u32::MAX;
+                    let mut end = 0;
+                    for (index, &member) in members.iter().enumerate() {
+                        let decorations = self.member_decorations.get(&(pointee, index as u32));
+                        let member_offset = decorations.and_then(|decorations| decorations.offset).unwrap_or(0);
+                        let matrix_stride = decorations.and_then(|decorations| decorations.matrix_stride);
+                        offset = offset.min(member_offset);
+                        end = end.max(member_offset + self.type_size(member, matrix_stride)?);
+                    }
+                    if offset < end {
+                        reflection.push_constants = Some(PushConstantRange {stages: stage, offset, size: end - offset});
+                    }
+                }
+                _ => ()
+            }
+        }
+
+        Ok(reflection)
+    }
+
+
+    fn interface_variable(&self, id: u32, location: u32, type_id: u32) -> Result<InterfaceVariable> {
+        let name = self.variable_name(id);
+        let unsupported = || invalid(self.name, &format!("the type of {} is not supported at a stage interface", name));
+
+        let (column, locations) = match self.types.get(&type_id) {
+            Some(Type::Matrix {column, count}) => (*column, *count),
+            _ => (type_id, 1)
+        };
+        let (component, count) = match self.types.get(&column) {
+            Some(Type::Vector {component, count}) => (*component, *count),
+            _ => (column, 1)
+        };
+
+        let format = match (self.types.get(&component), count) {
+            (Some(Type::Float {width: 32}), 1) => vk::VK_FORMAT_R32_SFLOAT,
+            (Some(Type::Float {width: 32}), 2) => vk::VK_FORMAT_R32G32_SFLOAT,
+            (Some(Type::Float {width: 32}), 3) => vk::VK_FORMAT_R32G32B32_SFLOAT,
+            (Some(Type::Float {width: 32}), 4) => vk::VK_FORMAT_R32G32B32A32_SFLOAT,
+            (Some(Type::Int {width: 32, signed: true}), 1) => vk::VK_FORMAT_R32_SINT,
+            (Some(Type::Int {width: 32, signed: true}), 2) => vk::VK_FORMAT_R32G32_SINT,
+            (Some(Type::Int {width: 32, signed: true}), 3) => vk::VK_FORMAT_R32G32B32_SINT,
+            (Some(Type::Int {width: 32, signed: true}), 4) => vk::VK_FORMAT_R32G32B32A32_SINT,
+            (Some(Type::Int {width: 32, signed: false}), 1) => vk::VK_FORMAT_R32_UINT,
+            (Some(Type::Int {width: 32, signed: false}), 2) => vk::VK_FORMAT_R32G32_UINT,
+            (Some(Type::Int {width: 32, signed: false}), 3) => vk::VK_FORMAT_R32G32B32_UINT,
+            (Some(Type::Int {width: 32, signed: false}), 4) => vk::VK_FORMAT_R32G32B32A32_UINT,
+            _ => return unsupported()
+        };
+
+        Ok(InterfaceVariable {location, locations, format, size: locations * count * 4, name})
+    }
+
+
+    /// The descriptor type and count of a resource variable pointing at `type_id`.
+    fn descriptor_type(&self, type_id: u32, storage_class: u32) -> Result<(vk::VkDescriptorType, u32)> {
+        let (element, count) = match self.types.get(&type_id) {
+            Some(Type::Array {element, length}) => (*element, *length),
+            Some(Type::RuntimeArray) => return invalid(self.name, "runtime sized descriptor arrays are not supported"),
+            _ => (type_id, 1)
+        };
+        let decorations = self.decorations.get(&element);
+
+        let descriptor_type = match (self.types.get(&element), storage_class) {
+            (Some(Type::Struct {..}), STORAGE_CLASS_STORAGE_BUFFER) => vk::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
+            (Some(Type::Struct {..}), _) if decorations.is_some_and(|decorations| decorations.buffer_block) =>
+                vk::VK_DESCRIPTOR_TYPE_STORAGE_BUFFER,
+            (Some(Type::Struct {..}), _) if decorations.is_some_and(|decorations| decorations.block) =>
+                vk::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER,
+            (Some(Type::SampledImage), _) => vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER,
+            (Some(Type::Sampler), _) => vk::VK_DESCRIPTOR_TYPE_SAMPLER,
+            (Some(Type::Image {dim: DIM_SUBPASS_DATA, ..}), _) => vk::VK_DESCRIPTOR_TYPE_INPUT_ATTACHMENT,
+            (Some(Type::Image {dim: DIM_BUFFER, sampled: 2}), _) => vk::VK_DESCRIPTOR_TYPE_STORAGE_TEXEL_BUFFER,
+            (Some(Type::Image {dim: DIM_BUFFER, ..}), _) => vk::VK_DESCRIPTOR_TYPE_UNIFORM_TEXEL_BUFFER,
+            (Some(Type::Image {sampled: 2, ..}), _) => vk::VK_DESCRIPTOR_TYPE_STORAGE_IMAGE,
+            (Some(Type::Image {..}), _) => vk::VK_DESCRIPTOR_TYPE_SAMPLED_IMAGE,
+            _ => return invalid(self.name, "a resource variable has a type that no descriptor type matches")
+        };
+
+        Ok((descriptor_type, count))
+    }
+
+
+    /// Size of a type as laid out in a block. `matrix_stride` comes from the member holding a matrix.
+    fn type_size(&self, type_id: u32, matrix_stride: Option<u32>) -> Result<u32> {
+        match self.types.get(&type_id) {
+            Some(Type::Bool) => Ok(4),
+            Some(Type::Int {width, ..}) | Some(Type::Float {width}) => Ok(width / 8),
+            Some(Type::Vector {component, count}) => Ok(count * self.type_size(*component, None)?),
+            Some(Type::Matrix {column, count}) => match matrix_stride {
+                Some(stride) => Ok(count * stride),
+                None => Ok(count * self.type_size(*column, None)?)
+            },
+            Some(Type::Array {element, length}) => {
+                let stride = self.decorations.get(&type_id).and_then(|decorations| decorations.array_stride);
+                match stride {
+                    Some(stride) => Ok(length * stride),
+                    None => Ok(length * self.type_size(*element, matrix_stride)?)
+                }
+            }
+            Some(Type::Struct {members}) => {
+                let mut size = 0;
+                for (index, &member) in members.iter().enumerate() {
+                    let decorations = self.member_decorations.get(&(type_id, index as u32));
+                    let offset = decorations.and_then(|decorations| decorations.offset).unwrap_or(size);
+                    let matrix_stride = decorations.and_then(|decorations| decorations.matrix_stride);
+                    size = size.max(offset + self.type_size(member, matrix_stride)?);
+                }
+                Ok(size)
+            }
+            _ => invalid(self.name, "a block member has a type without a known size")
+        }
+    }
+
+
+    fn variable_name(&self, id: u32) -> String {
+        match self.names.get(&id) {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ => format!("%{}", id)
+        }
+    }
+}
+
+
+/// The pipeline stage an entry point runs in. Only the execution models of graphics and compute shaders are
+/// accepted, kernels and extension stages are not.
+fn stage_of(entry_point: &EntryPoint, name: &str) -> Result<vk::VkShaderStageFlags> {
+    let stage = match entry_point.execution_model {
+        0 => vk::VK_SHADER_STAGE_VERTEX_BIT,
+        1 => vk::VK_SHADER_STAGE_TESSELLATION_CONTROL_BIT,
+        2 => vk::VK_SHADER_STAGE_TESSELLATION_EVALUATION_BIT,
+        3 => vk::VK_SHADER_STAGE_GEOMETRY_BIT,
+        4 => vk::VK_SHADER_STAGE_FRAGMENT_BIT,
+        5 => vk::VK_SHADER_STAGE_COMPUTE_BIT,
+        model => return malformed(name, &format!(
+            "entry point {} has execution model {}, which is not a graphics or compute stage", entry_point.name, model))
+    };
+
+    Ok(stage as vk::VkShaderStageFlags)
+}
+
+
+/// Decodes a nul-terminated string packed into words, returning it and the number of words it used.
+fn literal_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+    for (index, word) in words.iter().enumerate() {
+        for byte in word.to_le_bytes() {
+            if byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), index + 1);
+            }
+            bytes.push(byte);
+        }
+    }
+
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+
+fn malformed<T>(name: &str, reason: &str) -> Result<T> {
+    Err(AppError::InvalidSpirv {stage: Stage::Shaders, path: name.to_string(), reason: reason.to_string()})
+}
+
+
+fn invalid<T>(name: &str, context: &str) -> Result<T> {
+    Err(AppError::Unsupported {stage: Stage::Shaders, context: format!("{}: {}", name, context)})
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXECUTION_MODEL_VERTEX: u32 = 0;
+    const EXECUTION_MODEL_FRAGMENT: u32 = 4;
+    const OP_I_ADD: u32 = 128;
+
+    fn op(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![(operands.len() as u32 + 1) << 16 | opcode];
+        words.extend(operands);
+        words
+    }
+
+
+    fn entry_point(execution_model: u32, name: &str) -> Vec<u32> {
+        let mut bytes = name.as_bytes().to_vec();
+        bytes.resize(name.len() / 4 * 4 + 4, 0);
+        let mut operands = vec![execution_model, 1];
+        operands.extend(bytes.chunks_exact(4).map(|word| u32::from_le_bytes(word.try_into().unwrap())));
+        op(OP_ENTRY_POINT, &operands)
+    }
+
+
+    // a SPIR-V 1.0 module with an id bound of 100
+    fn module(instructions: &[Vec<u32>]) -> Vec<u32> {
+        let mut words = vec![SPIRV_MAGIC, 0x0001_0000, 0, 100, 0];
+        words.extend(instructions.concat());
+        words
+    }
+
+
+    // a fragment shader sampling an array of textures at set 0 binding 1, `length` being the id of its length
+    fn texture_array(length: &[Vec<u32>]) -> Vec<u32> {
+        let mut instructions = vec![
+            entry_point(EXECUTION_MODEL_FRAGMENT, "main"),
+            op(OP_TYPE_FLOAT, &[2, 32]),
+            op(OP_TYPE_IMAGE, &[3, 2, 1, 0, 0, 0, 1, 0]),
+            op(OP_TYPE_SAMPLED_IMAGE, &[4, 3]),
+            op(OP_TYPE_INT, &[5, 32, 0])
+        ];
+        instructions.extend_from_slice(length);
+        instructions.extend([
+            op(OP_TYPE_ARRAY, &[7, 4, 6]),
+            op(OP_TYPE_POINTER, &[8, STORAGE_CLASS_UNIFORM_CONSTANT, 7]),
+            op(OP_VARIABLE, &[8, 9, STORAGE_CLASS_UNIFORM_CONSTANT]),
+            op(OP_DECORATE, &[9, DECORATION_DESCRIPTOR_SET, 0]),
+            op(OP_DECORATE, &[9, DECORATION_BINDING, 1])
+        ]);
+        module(&instructions)
+    }
+
+
+    fn bytes(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+
+    // the reason `validate` gives for rejecting `code`
+    fn rejection(code: &[u8]) -> String {
+        match validate(code, "shader.spv") {
+            Err(AppError::InvalidSpirv {reason, ..}) => reason,
+            result => panic!("expected invalid SPIR-V, got {:?}", result)
+        }
+    }
+
+
+    fn variable(location: u32, format: vk::VkFormat, name: &str) -> InterfaceVariable {
+        InterfaceVariable {location, locations: 1, format, size: 16, name: name.to_string()}
+    }
+
+
+    fn stage(stage: vk::VkShaderStageFlagBits) -> ShaderReflection {
+        ShaderReflection {
+            stage: stage as vk::VkShaderStageFlags,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            descriptor_bindings: Vec::new(),
+            push_constants: None
+        }
+    }
+
+
+    #[test]
+    fn spec_constant_array_lengths_take_the_default_value() {
+        let words = texture_array(&[op(OP_SPEC_CONSTANT, &[5, 6, 3])]);
+        let reflection = reflect(&words, "textures.frag").unwrap();
+        assert_eq!(reflection.descriptor_bindings[0].count, 3);
+        assert_eq!(reflection.descriptor_bindings[0].descriptor_type, vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER);
+    }
+
+
+    #[test]
+    fn spec_constant_op_array_lengths_are_rejected() {
+        let words = texture_array(&[op(OP_SPEC_CONSTANT, &[5, 10, 3]), op(OP_SPEC_CONSTANT_OP, &[5, 6, OP_I_ADD, 10, 10])]);
+        assert!(matches!(reflect(&words, "textures.frag"), Err(AppError::Unsupported {..})));
+    }
+
+
+    #[test]
+    fn fragment_inputs_have_to_be_written_by_the_vertex_stage() {
+        let mut vertex = stage(vk::VK_SHADER_STAGE_VERTEX_BIT);
+        vertex.outputs.push(variable(0, vk::VK_FORMAT_R32G32B32_SFLOAT, "fragColor"));
+        let mut fragment = stage(vk::VK_SHADER_STAGE_FRAGMENT_BIT);
+        fragment.inputs.push(variable(1, vk::VK_FORMAT_R32G32B32_SFLOAT, "fragColor"));
+
+        assert!(PipelineInterface::new(&vertex, &fragment, "shaders").is_err());
+    }
+
+
+    #[test]
+    fn fragment_inputs_have_to_match_the_vertex_output_format() {
+        let mut vertex = stage(vk::VK_SHADER_STAGE_VERTEX_BIT);
+        vertex.outputs.push(variable(0, vk::VK_FORMAT_R32G32B32_SFLOAT, "fragColor"));
+        let mut fragment = stage(vk::VK_SHADER_STAGE_FRAGMENT_BIT);
+        fragment.inputs.push(variable(0, vk::VK_FORMAT_R32G32B32A32_SFLOAT, "fragColor"));
+        assert!(PipelineInterface::new(&vertex, &fragment, "shaders").is_err());
+
+        fragment.inputs[0].format = vk::VK_FORMAT_R32G32B32_SFLOAT;
+        assert!(PipelineInterface::new(&vertex, &fragment, "shaders").is_ok());
+    }
+
+
+    #[test]
+    fn push_constant_blocks_of_both_stages_share_one_range() {
+        let mut vertex = stage(vk::VK_SHADER_STAGE_VERTEX_BIT);
+        vertex.push_constants = Some(PushConstantRange {stages: vertex.stage, offset: 16, size: 64});
+        let mut fragment = stage(vk::VK_SHADER_STAGE_FRAGMENT_BIT);
+        fragment.push_constants = Some(PushConstantRange {stages: fragment.stage, offset: 0, size: 32});
+
+        let interface = PipelineInterface::new(&vertex, &fragment, "shaders").unwrap();
+        let stages = (vk::VK_SHADER_STAGE_VERTEX_BIT | vk::VK_SHADER_STAGE_FRAGMENT_BIT) as vk::VkShaderStageFlags;
+        assert_eq!(interface.push_constant_ranges, vec![PushConstantRange {stages, offset: 0, size: 80}]);
+    }
+
+
+    #[test]
+    fn modules_with_a_main_entry_point_are_valid() {
+        let words = module(&[entry_point(EXECUTION_MODEL_VERTEX, "main")]);
+        assert_eq!(validate(&bytes(&words), "shader.spv").unwrap(), words);
+    }
+
+
+    #[test]
+    fn lengths_have_to_be_whole_words() {
+        let mut code = bytes(&module(&[entry_point(EXECUTION_MODEL_VERTEX, "main")]));
+        code.push(0);
+        assert!(rejection(&code).contains("multiple of 4"));
+    }
+
+
+    #[test]
+    fn the_magic_number_has_to_match() {
+        let mut words = module(&[entry_point(EXECUTION_MODEL_VERTEX, "main")]);
+        words[0] = 0x0203_0723;
+        assert!(rejection(&bytes(&words)).contains("magic number"));
+    }
+
+
+    #[test]
+    fn byte_swapped_modules_are_read_in_host_order() {
+        let words = module(&[entry_point(EXECUTION_MODEL_VERTEX, "main")]);
+        let swapped: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes()).collect();
+        assert_eq!(validate(&swapped, "shader.spv").unwrap(), words);
+    }
+
+
+    #[test]
+    fn versions_after_1_6_are_rejected() {
+        let mut words = module(&[entry_point(EXECUTION_MODEL_VERTEX, "main")]);
+        words[1] = 0x0001_0600;
+        assert!(validate(&bytes(&words), "shader.spv").is_ok());
+        words[1] = 0x0001_0700;
+        assert!(rejection(&bytes(&words)).contains("version 1.7"));
+    }
+
+
+    #[test]
+    fn the_id_bound_cannot_be_0() {
+        let mut words = module(&[entry_point(EXECUTION_MODEL_VERTEX, "main")]);
+        words[3] = 0;
+        assert!(rejection(&bytes(&words)).contains("bound is 0"));
+    }
+
+
+    #[test]
+    fn instructions_cannot_run_past_the_end() {
+        let mut words = module(&[entry_point(EXECUTION_MODEL_VERTEX, "main"), op(OP_NAME, &[1, 0])]);
+        let last = words.len() - 3;
+        words[last] += 1 << 16;
+        assert!(rejection(&bytes(&words)).contains("invalid length"));
+    }
+
+
+    #[test]
+    fn a_main_entry_point_is_required() {
+        let words = module(&[entry_point(EXECUTION_MODEL_VERTEX, "other")]);
+        assert!(rejection(&bytes(&words)).contains("no entry point named main"));
+        assert!(rejection(&bytes(&module(&[]))).contains("no entry point"));
+    }
+}
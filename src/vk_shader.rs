@@ -0,0 +1,347 @@
+use crate::vk_error::{AppError, Result, Stage};
+use crate::vk_reflect::validate;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// how often the shader directories are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+// files in the watched directories that can affect a shader
+const WATCHED_EXTENSIONS: [&str; 8] = ["vert", "frag", "comp", "geom", "tesc", "tese", "glsl", "spv"];
+
+/// Environment variable naming a directory whose shaders replace the embedded ones. Only the GLSL files
+/// `shader.vert` and `shader.frag` are taken from it, SPIR-V files next to them are ignored.
+pub const SHADER_DIR_VAR: &str = "HELLO_TRIANGLE_SHADER_DIR";
+
+// the files of `shaders/`, so the default pipeline does not depend on the working directory
+const EMBEDDED_SHADERS: [(&str, &str); 3] = [
+    ("shader.vert", include_str!("../shaders/shader.vert")),
+    ("shader.frag", include_str!("../shaders/shader.frag")),
+    ("draw_push_constants.glsl", include_str!("../shaders/draw_push_constants.glsl"))
+];
+
+/// Where the code of a shader stage comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShaderSource {
+    /// GLSL compiled at runtime. The stage is taken from the extension: `.vert`, `.frag`, `.comp`, `.geom`,
+    /// `.tesc` or `.tese`.
+    Glsl(PathBuf),
+    /// Precompiled SPIR-V, loaded as is.
+    Spirv(PathBuf),
+    /// A GLSL file of `shaders/` that was embedded into the binary at build time, compiled at runtime like `Glsl`.
+    /// Its includes resolve to the other embedded files.
+    Embedded(&'static str)
+}
+
+impl ShaderSource {
+    /// The GLSL file `name` of `shader_dir` if there is one, and the embedded file of that name otherwise.
+    pub fn from_dir_or_embedded(name: &'static str, shader_dir: &Path) -> Self {
+        let path = shader_dir.join(name);
+        if path.is_file() {ShaderSource::Glsl(path)} else {ShaderSource::Embedded(name)}
+    }
+
+
+    /// The shader at `path`, precompiled SPIR-V if it ends in `.spv` and GLSL otherwise.
+    pub fn from_file(path: PathBuf) -> Self {
+        if path.extension().is_some_and(|extension| extension == "spv") {
+            ShaderSource::Spirv(path)
+        } else {
+            ShaderSource::Glsl(path)
+        }
+    }
+
+
+    /// The file the source is read from, `None` for embedded sources.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ShaderSource::Glsl(path) | ShaderSource::Spirv(path) => Some(path),
+            ShaderSource::Embedded(_) => None
+        }
+    }
+
+
+    /// Names the source in messages.
+    pub fn name(&self) -> String {
+        match self {
+            ShaderSource::Glsl(path) | ShaderSource::Spirv(path) => path.display().to_string(),
+            ShaderSource::Embedded(name) => format!("{} (embedded)", name)
+        }
+    }
+
+
+    /// Reads the source and returns validated SPIR-V words, compiling it first if it is GLSL.
+    pub fn load(&self) -> Result<Vec<u32>> {
+        let code = match self {
+            ShaderSource::Glsl(path) => compile_glsl(path)?,
+            ShaderSource::Spirv(path) => read_file(path)?,
+            ShaderSource::Embedded(name) => compile_embedded(name)?
+        };
+
+        validate(&code, &self.name())
+    }
+}
+
+
+/// Notices changes to shader files by polling modification times.
+///
+/// Watches every shader source, include or SPIR-V file in the directories of the given shaders, so edits to
+/// included files are noticed as well. Files that appear or disappear count as changes.
+pub struct ShaderWatcher {
+    directories: Vec<PathBuf>,
+    modified: Vec<(PathBuf, SystemTime)>,
+    last_poll: Instant
+}
+
+impl ShaderWatcher {
+    pub fn new(shaders: &[&Path]) -> Self {
+        let mut directories: Vec<PathBuf> = shaders.iter()
+            .map(|shader| shader.parent().map(Path::to_path_buf).unwrap_or_default())
+            .collect();
+        directories.sort();
+        directories.dedup();
+
+        let modified = scan(&directories);
+        Self {directories, modified, last_poll: Instant::now()}
+    }
+
+
+    /// Whether any watched file changed since the previous scan. Cheap to call every frame, the directories are
+    /// only scanned once per `WATCH_INTERVAL`.
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < WATCH_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let modified = scan(&self.directories);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+
+        true
+    }
+}
+
+
+/// Modification times of the watched files in `directories`, sorted by path. Unreadable entries are skipped.
+fn scan(directories: &[PathBuf]) -> Vec<(PathBuf, SystemTime)> {
+    let mut modified = Vec::new();
+    for directory in directories {
+        let directory = if directory.as_os_str().is_empty() {Path::new(".")} else {directory.as_path()};
+        let Ok(entries) = std::fs::read_dir(directory) else {continue};
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let watched = path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| WATCHED_EXTENSIONS.contains(&extension));
+            if !watched {continue}
+
+            if let Ok(time) = entry.metadata().and_then(|metadata| metadata.modified()) {
+                modified.push((path, time));
+            }
+        }
+    }
+    modified.sort();
+
+    modified
+}
+
+
+/// Compiles a GLSL file into SPIR-V for Vulkan.
+///
+/// `#include "file"` is resolved relative to the including file, and `#include <file>` relative to the directory
+/// of the file being compiled. Includes that are not found there fall back to the embedded files of the same name.
+/// Compile errors name the file and line they occurred at, warnings are printed.
+pub fn compile_glsl(path: &Path) -> Result<Vec<u8>> {
+    let source = String::from_utf8(read_file(path)?).map_err(|error| AppError::Io {
+        stage: Stage::Shaders,
+        path: path.display().to_string(),
+        source: std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    })?;
+
+    let include_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    compile(&source, path, Some(include_dir))
+}
+
+
+/// Compiles one of the embedded GLSL files, whose includes only resolve to other embedded files.
+fn compile_embedded(name: &str) -> Result<Vec<u8>> {
+    let Some(source) = embedded_source(name) else {
+        return Err(AppError::Unsupported {
+            stage: Stage::Shaders,
+            context: format!("there is no embedded shader named {}", name)
+        });
+    };
+
+    compile(source, Path::new(name), None)
+}
+
+
+/// Compiles GLSL read from `path`. Includes are looked up on disk only if `include_dir` is given.
+fn compile(source: &str, path: &Path, include_dir: Option<PathBuf>) -> Result<Vec<u8>> {
+    let kind = shader_kind(path)?;
+    let compiler = shaderc::Compiler::new().ok_or_else(|| unsupported_compiler(path))?;
+    let mut options = shaderc::CompileOptions::new().ok_or_else(|| unsupported_compiler(path))?;
+    options.set_target_env(shaderc::TargetEnv::Vulkan, shaderc::EnvVersion::Vulkan1_0 as u32);
+
+    options.set_include_callback(move |requested, include_type, requesting, _depth| {
+        let mut disk_error = None;
+        if let Some(include_dir) = &include_dir {
+            let base = match include_type {
+                shaderc::IncludeType::Relative => Path::new(requesting).parent().map(Path::to_path_buf).unwrap_or_default(),
+                shaderc::IncludeType::Standard => include_dir.clone()
+            };
+            let resolved = base.join(requested);
+
+            match std::fs::read_to_string(&resolved) {
+                Ok(content) => return Ok(shaderc::ResolvedInclude {resolved_name: resolved.display().to_string(), content}),
+                Err(error) => disk_error = Some(format!("could not include {}: {}", resolved.display(), error))
+            }
+        }
+
+        match embedded_source(requested) {
+            Some(content) => Ok(shaderc::ResolvedInclude {resolved_name: requested.to_string(), content: content.to_string()}),
+            None => Err(disk_error.unwrap_or_else(|| format!("there is no embedded file named {} to include", requested)))
+        }
+    });
+
+    let artifact = compiler
+        .compile_into_spirv(source, kind, &path.display().to_string(), "main", Some(&options))
+        .map_err(|error| AppError::Compile {
+            stage: Stage::Shaders,
+            path: path.display().to_string(),
+            // compilation errors are already formatted as `file:line: error: message`
+            log: match error {
+                shaderc::Error::CompilationError(_, log) => log.trim_end().to_string(),
+                error => error.to_string()
+            }
+        })?;
+
+    if artifact.get_num_warnings() > 0 {
+        eprintln!("{}", artifact.get_warning_messages().trim_end());
+    }
+
+    Ok(artifact.as_binary_u8().to_vec())
+}
+
+
+fn embedded_source(name: &str) -> Option<&'static str> {
+    EMBEDDED_SHADERS.iter().find(|(embedded_name, _)| *embedded_name == name).map(|(_, source)| *source)
+}
+
+
+fn shader_kind(path: &Path) -> Result<shaderc::ShaderKind> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("vert") => Ok(shaderc::ShaderKind::Vertex),
+        Some("frag") => Ok(shaderc::ShaderKind::Fragment),
+        Some("comp") => Ok(shaderc::ShaderKind::Compute),
+        Some("geom") => Ok(shaderc::ShaderKind::Geometry),
+        Some("tesc") => Ok(shaderc::ShaderKind::TessControl),
+        Some("tese") => Ok(shaderc::ShaderKind::TessEvaluation),
+        _ => Err(AppError::Unsupported {
+            stage: Stage::Shaders,
+            context: format!("cannot tell the shader stage of {} from its extension", path.display())
+        })
+    }
+}
+
+
+fn unsupported_compiler(path: &Path) -> AppError {
+    AppError::Unsupported {
+        stage: Stage::Shaders,
+        context: format!("could not initialize the shader compiler for {}", path.display())
+    }
+}
+
+
+/// Checks that `shader_dir` exists and is a directory, so a mistyped path is not taken for one without shaders.
+pub fn check_shader_dir(shader_dir: &Path) -> Result<()> {
+    let io_error = |source| AppError::Io {stage: Stage::Shaders, path: shader_dir.display().to_string(), source};
+    let metadata = std::fs::metadata(shader_dir).map_err(io_error)?;
+    if !metadata.is_dir() {
+        return Err(io_error(std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a directory")));
+    }
+
+    Ok(())
+}
+
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|source| AppError::Io {
+        stage: Stage::Shaders,
+        path: path.display().to_string(),
+        source
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vk_mesh::Vertex;
+    use crate::vk_push::{DrawContext, DrawPushConstants};
+    use crate::vk_reflect::{reflect, PipelineInterface};
+    use vulkan::vulkan as vk;
+
+    // the default pipeline is built from the embedded shaders, so they have to keep matching the host side types
+    fn embedded_interface() -> PipelineInterface {
+        let vertex_code = ShaderSource::Embedded("shader.vert").load().unwrap();
+        let fragment_code = ShaderSource::Embedded("shader.frag").load().unwrap();
+        let vertex = reflect(&vertex_code, "shader.vert").unwrap();
+        let fragment = reflect(&fragment_code, "shader.frag").unwrap();
+
+        PipelineInterface::new(&vertex, &fragment, "embedded shaders").unwrap()
+    }
+
+
+    #[test]
+    fn embedded_shaders_take_the_vertex_layout() {
+        let interface = embedded_interface();
+        assert!(interface.matches_vertex_input(&Vertex::binding_description(), &Vertex::attribute_descriptions()));
+    }
+
+
+    #[test]
+    fn embedded_shaders_read_the_uniform_buffer() {
+        let interface = embedded_interface();
+        let binding = interface.descriptor_bindings.iter().find(|binding| binding.set == 0 && binding.binding == 0).unwrap();
+        assert_eq!(binding.descriptor_type, vk::VK_DESCRIPTOR_TYPE_UNIFORM_BUFFER);
+        assert_eq!(binding.stages, vk::VK_SHADER_STAGE_VERTEX_BIT as vk::VkShaderStageFlags);
+    }
+
+
+    #[test]
+    fn embedded_shaders_declare_the_draw_push_constants() {
+        let interface = embedded_interface();
+        let range = DrawPushConstants::range();
+        let draw = DrawContext::new(std::ptr::null_mut(), std::ptr::null_mut(), &interface.push_constant_ranges);
+        assert!(draw.covers(range.stages, range.offset, range.size));
+    }
+
+
+    #[test]
+    fn embedded_shaders_sample_the_texture() {
+        let interface = embedded_interface();
+        let binding = interface.descriptor_bindings.iter().find(|binding| binding.set == 0 && binding.binding == 1).unwrap();
+        assert_eq!(binding.descriptor_type, vk::VK_DESCRIPTOR_TYPE_COMBINED_IMAGE_SAMPLER);
+        assert_eq!(binding.stages, vk::VK_SHADER_STAGE_FRAGMENT_BIT as vk::VkShaderStageFlags);
+    }
+
+
+    #[test]
+    fn shader_files_are_spirv_only_with_the_spv_extension() {
+        assert_eq!(ShaderSource::from_file("a/shader.vert.spv".into()), ShaderSource::Spirv("a/shader.vert.spv".into()));
+        assert_eq!(ShaderSource::from_file("a/shader.vert".into()), ShaderSource::Glsl("a/shader.vert".into()));
+        assert_eq!(ShaderSource::from_file("a/spv".into()), ShaderSource::Glsl("a/spv".into()));
+    }
+
+
+    #[test]
+    fn shader_dirs_have_to_be_directories() {
+        assert!(check_shader_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders").as_path()).is_ok());
+        assert!(check_shader_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders/shader.vert").as_path()).is_err());
+        assert!(check_shader_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("no such directory").as_path()).is_err());
+    }
+}
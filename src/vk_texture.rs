@@ -0,0 +1,190 @@
+use crate::vk_error::{AppError, Result, Stage};
+use std::path::Path;
+
+/// Decoded texture pixels, as tightly packed RGBA8 rows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>
+}
+
+impl TextureData {
+    /// Decodes a PNG or JPEG file. Images without an alpha channel get an opaque one.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path).map_err(|error| AppError::Io {
+            stage: Stage::Textures,
+            path: path.display().to_string(),
+            source: match error {
+                image::ImageError::IoError(source) => source,
+                error => std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+            }
+        })?;
+
+        let image = image.into_rgba8();
+        Ok(Self {width: image.width(), height: image.height(), pixels: image.into_raw()})
+    }
+
+
+    /// A single opaque white texel, which leaves vertex colors unchanged when sampled.
+    pub fn white() -> Self {
+        Self {width: 1, height: 1, pixels: vec![255; 4]}
+    }
+
+
+    pub fn size(&self) -> usize {
+        self.pixels.len()
+    }
+
+
+    /// Length of the full mip chain, down to a single texel.
+    pub fn mip_levels(&self) -> u32 {
+        32 - self.width.max(self.height).max(1).leading_zeros()
+    }
+
+
+    /// The next mip level, each texel averaging a 2x2 block of this one. The pixels are taken to be sRGB
+    /// encoded and are averaged in linear space, like a linear blit of an sRGB image does.
+    pub fn downsampled(&self) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+
+        for y in 0..height {
+            for x in 0..width {
+                // odd extents clamp the block to the last row or column
+                let xs = [(x * 2).min(self.width - 1), (x * 2 + 1).min(self.width - 1)];
+                let ys = [(y * 2).min(self.height - 1), (y * 2 + 1).min(self.height - 1)];
+
+                for channel in 0..4 {
+                    let mut sum = 0.0;
+                    for sy in ys {
+                        for sx in xs {
+                            let value = self.pixels[((sy * self.width + sx) * 4 + channel) as usize];
+                            sum += if channel == 3 {value as f32 / 255.0} else {srgb_to_linear(value)};
+                        }
+                    }
+                    let average = sum / 4.0;
+                    pixels.push(if channel == 3 {(average * 255.0).round() as u8} else {linear_to_srgb(average)});
+                }
+            }
+        }
+
+        Self {width, height, pixels}
+    }
+
+
+    /// This texture followed by every smaller mip level, as generated by `downsampled`.
+    pub fn mip_chain(&self) -> Vec<Self> {
+        let mut chain = vec![self.clone()];
+        for _ in 1..self.mip_levels() {
+            let next = chain.last().unwrap().downsampled();
+            chain.push(next);
+        }
+
+        chain
+    }
+}
+
+impl Default for TextureData {
+    fn default() -> Self {
+        Self::white()
+    }
+}
+
+
+/// Level of detail settings of the texture sampler.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerLod {
+    pub min_lod: f32,
+    /// `None` allows sampling the whole mip chain.
+    pub max_lod: Option<f32>,
+    /// Clamped to the device's `maxSamplerLodBias`.
+    pub mip_lod_bias: f32
+}
+
+impl Default for SamplerLod {
+    fn default() -> Self {
+        Self {min_lod: 0.0, max_lod: None, mip_lod_bias: 0.0}
+    }
+}
+
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(width: u32, height: u32, texels: &[[u8; 4]]) -> TextureData {
+        TextureData {width, height, pixels: texels.concat()}
+    }
+
+
+    #[test]
+    fn mip_chains_go_down_to_one_texel() {
+        assert_eq!(TextureData::white().mip_levels(), 1);
+        assert_eq!(texture(2, 1, &[[0; 4]; 2]).mip_levels(), 2);
+        assert_eq!(TextureData {width: 256, height: 256, pixels: Vec::new()}.mip_levels(), 9);
+        assert_eq!(TextureData {width: 300, height: 7, pixels: Vec::new()}.mip_levels(), 9);
+        assert_eq!(TextureData {width: 0, height: 0, pixels: Vec::new()}.mip_levels(), 1);
+    }
+
+
+    #[test]
+    fn mip_chain_has_one_texture_per_level() {
+        let chain = texture(4, 2, &[[255; 4]; 8]).mip_chain();
+        let extents: Vec<(u32, u32)> = chain.iter().map(|level| (level.width, level.height)).collect();
+        assert_eq!(extents, [(4, 2), (2, 1), (1, 1)]);
+        assert!(chain.iter().all(|level| level.pixels.iter().all(|&channel| channel == 255)));
+    }
+
+
+    #[test]
+    fn downsampling_averages_in_linear_space() {
+        let black = [0, 0, 0, 0];
+        let white = [255, 255, 255, 255];
+        let level = texture(2, 2, &[black, white, white, black]).downsampled();
+        assert_eq!((level.width, level.height), (1, 1));
+        // linear 0.5 is 188 in sRGB, alpha is not sRGB encoded
+        assert_eq!(level.pixels, [188, 188, 188, 128]);
+    }
+
+
+    #[test]
+    fn downsampling_clamps_odd_extents() {
+        let level = texture(1, 3, &[[10, 20, 30, 40], [10, 20, 30, 40], [200; 4]]).downsampled();
+        assert_eq!((level.width, level.height), (1, 1));
+        assert_eq!(level.pixels, [10, 20, 30, 40]);
+
+        let level = TextureData::white().downsampled();
+        assert_eq!(level, TextureData::white());
+    }
+
+
+    #[test]
+    fn srgb_conversion_round_trips() {
+        for value in 0..=255 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+        }
+    }
+}
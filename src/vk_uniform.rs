@@ -0,0 +1,60 @@
+/// Column-major, as GLSL expects it: `m[column][row]`.
+pub type Mat4 = [[f32; 4]; 4];
+
+pub const IDENTITY: Mat4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0]
+];
+
+
+/// Mirrors the uniform block at binding 0 of `shaders/shader.vert`, laid out for std140.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UniformBufferObject {
+    pub model: Mat4,
+    pub view: Mat4,
+    pub proj: Mat4,
+    /// Seconds since rendering started.
+    pub time: f32,
+    // std140 rounds the block size up to a multiple of 16
+    _padding: [f32; 3]
+}
+
+impl UniformBufferObject {
+    pub fn new(model: Mat4, view: Mat4, proj: Mat4, time: f32) -> Self {
+        Self {model, view, proj, time, _padding: [0.0; 3]}
+    }
+}
+
+impl Default for UniformBufferObject {
+    fn default() -> Self {
+        Self::new(IDENTITY, IDENTITY, IDENTITY, 0.0)
+    }
+}
+
+
+/// Projection that keeps clip space square on a `width` x `height` target, so geometry is not
+/// stretched when the window is resized.
+pub fn aspect_correction(width: u32, height: u32) -> Mat4 {
+    if width == 0 || height == 0 {
+        return IDENTITY;
+    }
+    let aspect = width as f32 / height as f32;
+    if aspect > 1.0 {
+        scale(1.0 / aspect, 1.0, 1.0)
+    } else {
+        scale(1.0, aspect, 1.0)
+    }
+}
+
+
+pub fn scale(x: f32, y: f32, z: f32) -> Mat4 {
+    [
+        [x, 0.0, 0.0, 0.0],
+        [0.0, y, 0.0, 0.0],
+        [0.0, 0.0, z, 0.0],
+        [0.0, 0.0, 0.0, 1.0]
+    ]
+}
\ No newline at end of file
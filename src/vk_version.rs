@@ -0,0 +1,141 @@
+use vulkan::vulkan as vk;
+
+/// Environment variable capping the Vulkan version the instance asks for, e.g. `1.2`.
+pub const API_VERSION_VAR: &str = "HELLO_TRIANGLE_API_VERSION";
+
+pub const VULKAN_1_0: u32 = make_api_version(0, 1, 0, 0);
+pub const VULKAN_1_1: u32 = make_api_version(0, 1, 1, 0);
+pub const VULKAN_1_2: u32 = make_api_version(0, 1, 2, 0);
+pub const VULKAN_1_3: u32 = make_api_version(0, 1, 3, 0);
+
+/// The newest version the application makes use of, and the ceiling unless another one is set.
+pub const LATEST: u32 = VULKAN_1_3;
+
+/// Packs a version like `VK_MAKE_API_VERSION`. The variant is 0 for Vulkan itself.
+pub const fn make_api_version(variant: u32, major: u32, minor: u32, patch: u32) -> u32 {
+    variant << 29 | major << 22 | minor << 12 | patch
+}
+
+
+pub const fn api_version_variant(version: u32) -> u32 {
+    version >> 29
+}
+
+
+pub const fn api_version_major(version: u32) -> u32 {
+    version >> 22 & 0x7f
+}
+
+
+pub const fn api_version_minor(version: u32) -> u32 {
+    version >> 12 & 0x3ff
+}
+
+
+pub const fn api_version_patch(version: u32) -> u32 {
+    version & 0xfff
+}
+
+
+/// Parses `MAJOR.MINOR` or `MAJOR.MINOR.PATCH`, e.g. `1.2`.
+pub fn parse(value: &str) -> Option<u32> {
+    let parts: Vec<u32> = value.split('.').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    match parts[..] {
+        [major, minor] if major <= 0x7f && minor <= 0x3ff => Some(make_api_version(0, major, minor, 0)),
+        [major, minor, patch] if major <= 0x7f && minor <= 0x3ff && patch <= 0xfff =>
+            Some(make_api_version(0, major, minor, patch)),
+        _ => None
+    }
+}
+
+
+/// Formats a version as `MAJOR.MINOR.PATCH`, prefixed by the variant if it is not Vulkan's.
+pub fn name(version: u32) -> String {
+    let numbers = format!(
+        "{}.{}.{}",
+        api_version_major(version), api_version_minor(version), api_version_patch(version)
+    );
+    match api_version_variant(version) {
+        0 => numbers,
+        variant => format!("variant {} {}", variant, numbers)
+    }
+}
+
+
+/// The newest version the loader can create instances for. Vulkan 1.0 loaders do not have
+/// `vkEnumerateInstanceVersion`, so it is looked up instead of linked.
+pub fn instance_version() -> u32 {
+    let option_fn_ptr = unsafe{std::mem::transmute::<_, vk::PFN_vkEnumerateInstanceVersion>(
+        vk::vkGetInstanceProcAddr(std::ptr::null_mut(), "vkEnumerateInstanceVersion\0".as_ptr() as _))};
+    let Some(fn_ptr) = option_fn_ptr else {return VULKAN_1_0};
+
+    let mut version: u32 = 0;
+    if unsafe{fn_ptr(&mut version)} == vk::VK_SUCCESS {version} else {VULKAN_1_0}
+}
+
+
+/// The version to create the instance with: what the loader supports, capped at `ceiling`. Patch levels are
+/// dropped, they do not change which functionality is available.
+pub fn negotiate(instance_version: u32, ceiling: u32) -> u32 {
+    without_patch(instance_version).min(without_patch(ceiling))
+}
+
+
+/// The version the application can use on a device reporting `device_version`, which can be newer than the
+/// instance but then only offers what the instance version does.
+pub fn usable_on_device(instance_version: u32, device_version: u32) -> u32 {
+    instance_version.min(without_patch(device_version))
+}
+
+
+const fn without_patch(version: u32) -> u32 {
+    version & !0xfff
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn parse_accepts_two_or_three_parts() {
+        assert_eq!(parse("1.2"), Some(VULKAN_1_2));
+        assert_eq!(parse("1.2.3"), Some(make_api_version(0, 1, 2, 3)));
+        assert_eq!(parse("1"), None);
+        assert_eq!(parse("1.2.3.4"), None);
+        assert_eq!(parse(""), None);
+    }
+
+
+    #[test]
+    fn parse_rejects_out_of_range_and_non_numeric_parts() {
+        assert_eq!(parse("1.1023"), Some(make_api_version(0, 1, 1023, 0)));
+        assert_eq!(parse("1.1024"), None);
+        assert_eq!(parse("1.x"), None);
+        assert_eq!(parse("1.-2"), None);
+    }
+
+
+    #[test]
+    fn negotiate_drops_patch_levels_and_caps_at_the_ceiling() {
+        assert_eq!(negotiate(make_api_version(0, 1, 3, 250), LATEST), VULKAN_1_3);
+        assert_eq!(negotiate(make_api_version(0, 1, 2, 198), LATEST), VULKAN_1_2);
+        assert_eq!(negotiate(VULKAN_1_3, VULKAN_1_1), VULKAN_1_1);
+        assert_eq!(negotiate(VULKAN_1_3, make_api_version(0, 1, 2, 7)), VULKAN_1_2);
+    }
+
+
+    #[test]
+    fn devices_only_offer_what_the_instance_version_does() {
+        assert_eq!(usable_on_device(VULKAN_1_1, make_api_version(0, 1, 3, 250)), VULKAN_1_1);
+        assert_eq!(usable_on_device(VULKAN_1_3, make_api_version(0, 1, 2, 198)), VULKAN_1_2);
+    }
+
+
+    #[test]
+    fn names_show_the_variant_only_if_it_is_not_vulkans() {
+        assert_eq!(name(make_api_version(0, 1, 2, 198)), "1.2.198");
+        assert_eq!(name(make_api_version(1, 1, 0, 0)), "variant 1 1.0.0");
+    }
+}
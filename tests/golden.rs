@@ -0,0 +1,107 @@
+//! Golden-image regression tests.
+//!
+//! Each test renders through the binary's `--headless` mode at a fixed extent and compares the
+//! result against a PNG in `tests/golden/`. A Vulkan ICD is required, lavapipe is enough.
+//!
+//! Run with `UPDATE_GOLDEN=1` to overwrite the golden images with the current output, e.g. after an
+//! intended change to the pipeline or the shaders.
+
+use std::path::Path;
+use std::process::Command;
+
+const EXTENT: (u32, u32) = (256, 256);
+
+/// Largest per-channel difference for a pixel to still count as matching.
+const CHANNEL_TOLERANCE: u8 = 4;
+
+/// Number of mismatched pixels allowed before a test fails, to absorb rasterization differences on triangle edges.
+const MAX_MISMATCHED_PIXELS: usize = 16;
+
+
+#[test]
+fn triangle_matches_golden() {
+    check_golden("triangle", &[]);
+}
+
+
+fn check_golden(name: &str, extra_args: &[&str]) {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let output_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&output_dir).unwrap();
+
+    let actual_path = output_dir.join(format!("{name}.png"));
+    render(manifest_dir, &actual_path, extra_args);
+    let actual = image::open(&actual_path).unwrap().to_rgba8();
+
+    let golden_path = manifest_dir.join("tests").join("golden").join(format!("{name}.png"));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden_path.parent().unwrap()).unwrap();
+        actual.save(&golden_path).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&golden_path) {
+        Ok(expected) => expected.to_rgba8(),
+        Err(err) => panic!(
+            "could not load golden image {}: {err}\nrendered output is at {}, rerun with UPDATE_GOLDEN=1 to accept it",
+            golden_path.display(),
+            actual_path.display()
+        )
+    };
+
+    assert_eq!(
+        expected.dimensions(), actual.dimensions(),
+        "golden image {} has a different extent than the rendered output", golden_path.display()
+    );
+
+    let (mismatched, diff) = compare(&expected, &actual);
+    if mismatched > MAX_MISMATCHED_PIXELS {
+        let diff_path = output_dir.join(format!("{name}.diff.png"));
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{mismatched} pixels differ from {} by more than {CHANNEL_TOLERANCE}\nrendered: {}\ndiff: {}",
+            golden_path.display(),
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+
+fn render(working_dir: &Path, output: &Path, extra_args: &[&str]) {
+    let extent = format!("{}x{}", EXTENT.0, EXTENT.1);
+    let status = Command::new(env!("CARGO_BIN_EXE_vulkan-triangle"))
+        .current_dir(working_dir)
+        // the cache would be written to the user's cache directory
+        .args(["--headless", "--no-pipeline-cache", "--extent", &extent, "--output"])
+        .arg(output)
+        .args(extra_args)
+        .status()
+        .expect("failed to launch renderer");
+
+    assert!(status.success(), "headless render failed with {status}");
+}
+
+
+/// Returns the number of mismatched pixels, and an image with mismatches in red over a dimmed copy of the expected image.
+fn compare(expected: &image::RgbaImage, actual: &image::RgbaImage) -> (usize, image::RgbaImage) {
+    let mut mismatched = 0;
+    let diff = image::RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        let expected_pixel = expected.get_pixel(x, y);
+        let actual_pixel = actual.get_pixel(x, y);
+
+        let matches = expected_pixel.0.iter()
+            .zip(actual_pixel.0.iter())
+            .all(|(&e, &a)| e.abs_diff(a) <= CHANNEL_TOLERANCE);
+
+        if matches {
+            let [r, g, b, _] = expected_pixel.0;
+            image::Rgba([r / 4, g / 4, b / 4, 255])
+        } else {
+            mismatched += 1;
+            image::Rgba([255, 0, 0, 255])
+        }
+    });
+
+    (mismatched, diff)
+}